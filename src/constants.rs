@@ -1,2 +1,11 @@
-// Protocol version supported by this server implementation
-pub const SUPPORTED_PROTOCOL_VERSION: &str = "2024-11-05"; // Match client/spec
+use crate::types::ProtocolVersion;
+
+// Protocol versions supported by this server implementation, oldest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[ProtocolVersion] = &[
+    ProtocolVersion::V2024_11_05,
+    ProtocolVersion::V2025_03_26,
+    ProtocolVersion::V2025_06_18,
+];
+
+// Offered to clients that ask for a version we don't support
+pub const LATEST_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::V2025_06_18;
@@ -0,0 +1,173 @@
+use crate::peer::Peer;
+use crate::types::{
+    ClientCapabilities, CreateMessageRequestParams, CreateMessageResult, ElicitAction,
+    ElicitRequestParams, ElicitResult, ElicitationSchema, ProgressNotificationParams, Root,
+};
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Per-request data handed to handlers alongside their params.
+#[derive(Clone)]
+pub struct RequestContext {
+    request_id: Value,
+    peer: Peer,
+    cancellation: CancellationToken,
+    progress: Option<ProgressReporter>,
+}
+
+impl RequestContext {
+    pub(crate) fn new(
+        request_id: Value,
+        peer: Peer,
+        cancellation: CancellationToken,
+        progress_token: Option<Value>,
+    ) -> Self {
+        let progress = progress_token.map(|token| ProgressReporter::new(token, peer.clone()));
+        Self {
+            request_id,
+            peer,
+            cancellation,
+            progress,
+        }
+    }
+
+    /// The JSON-RPC id of the request being handled.
+    pub fn request_id(&self) -> &Value {
+        &self.request_id
+    }
+
+    /// Handle to the client that sent the request.
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    /// Cancelled when the client sends `notifications/cancelled` for this request.
+    ///
+    /// The server already drops the handler future at its next await point when this
+    /// fires, so checking it is only needed to stop blocking or spawned work early.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// What the client declared it supports, e.g. to check for sampling before using it.
+    pub fn client_capabilities(&self) -> ClientCapabilities {
+        self.peer.session().client_capabilities()
+    }
+
+    /// Asks the client's LLM to generate a message via `sampling/createMessage`.
+    ///
+    /// Fails without contacting the client if it did not advertise the `sampling`
+    /// capability. The client may show the request to the user, who can edit or reject it.
+    pub async fn create_message(&self, params: CreateMessageRequestParams) -> Result<CreateMessageResult> {
+        if !self.client_capabilities().supports_sampling() {
+            bail!("client does not support sampling");
+        }
+        let result = self
+            .peer
+            .request("sampling/createMessage", Some(serde_json::to_value(params)?))
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Asks the user for structured input through the client's UI via `elicitation/create`.
+    ///
+    /// Fails without contacting the client if it did not advertise the `elicitation`
+    /// capability, or if an accepted form is missing a required field.
+    pub async fn elicit(&self, message: impl Into<String>, schema: ElicitationSchema) -> Result<ElicitResult> {
+        if !self.client_capabilities().supports_elicitation() {
+            bail!("client does not support elicitation");
+        }
+        let required = schema.required.clone();
+        let params = ElicitRequestParams {
+            message: message.into(),
+            requested_schema: schema,
+        };
+        let result = self
+            .peer
+            .request("elicitation/create", Some(serde_json::to_value(params)?))
+            .await?;
+        let result: ElicitResult = serde_json::from_value(result)?;
+
+        if result.action == ElicitAction::Accept {
+            let content = result.content.as_ref();
+            let missing: Vec<&str> = required
+                .iter()
+                .filter(|name| !content.is_some_and(|c| c.contains_key(name.as_str())))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                bail!("client accepted elicitation without required fields: {}", missing.join(", "));
+            }
+        }
+        Ok(result)
+    }
+
+    /// The directories and files the client has opened, e.g. to confine filesystem access.
+    ///
+    /// Served from the session cache, which the server fills after initialization and
+    /// clears and refills on `notifications/roots/list_changed`; asks the client if
+    /// nothing is cached.
+    /// Fails if the client does not support roots.
+    pub async fn roots(&self) -> Result<Vec<Root>> {
+        match self.peer.session().roots() {
+            Some(roots) => Ok(roots),
+            None => self.peer.list_roots().await,
+        }
+    }
+
+    /// Reporter for `notifications/progress`, if the client asked for progress by sending
+    /// a `_meta.progressToken` with the request.
+    pub fn progress(&self) -> Option<&ProgressReporter> {
+        self.progress.as_ref()
+    }
+}
+
+// --- Progress Reporting ---
+
+/// Sends `notifications/progress` for a single request.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    token: Value,
+    peer: Peer,
+    last_progress: Arc<Mutex<Option<f64>>>, // The spec requires strictly increasing values
+}
+
+impl ProgressReporter {
+    fn new(token: Value, peer: Peer) -> Self {
+        Self {
+            token,
+            peer,
+            last_progress: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Reports `progress` out of an optional `total`, with an optional human-readable message.
+    ///
+    /// Values that do not increase on the previous report are dropped with a warning.
+    pub fn report(&self, progress: f64, total: Option<f64>, message: Option<String>) -> Result<()> {
+        {
+            let mut last = self.last_progress.lock().unwrap();
+            if last.is_some_and(|last| progress <= last) {
+                warn!("dropping non-increasing progress {} for token {}", progress, self.token);
+                return Ok(());
+            }
+            *last = Some(progress);
+        }
+
+        let params = ProgressNotificationParams {
+            progress_token: self.token.clone(),
+            progress,
+            total,
+            message,
+        };
+        self.peer
+            .notify("notifications/progress", Some(serde_json::to_value(params)?))
+    }
+}
@@ -1,10 +1,18 @@
-use crate::constants::SUPPORTED_PROTOCOL_VERSION;
+use crate::constants::{LATEST_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::context::RequestContext;
+use crate::pagination::{self, InvalidCursor};
+use crate::prompts::{PromptProvider, PromptRegistry};
+use crate::schema;
+use crate::resources::ResourceRegistry;
+use crate::session::{LifecycleState, Session};
+use crate::tools::{InvalidArguments, RegisteredTool, ToolRegistry};
 use crate::types::{
-    CallToolRequestParams, CallToolResult, ContentPart, InitializeRequestParams, InitializeResult,
-    ListPromptsResult, ListResourcesResult, ListToolsResult, Prompt, Resource, ServerCapabilities,
-    Implementation, Tool, ErrorData, InitializedNotificationParams,
+    CallToolRequestParams, CallToolResult, CancelledNotificationParams, EmptyResult, GetPromptRequestParams, GetPromptResult,
+    InitializeRequestParams, InitializeResult, ProtocolVersion,
+    ListPromptsResult, ListResourceTemplatesResult, ListResourcesResult, ListToolsResult, PaginatedRequestParams, ReadResourceRequestParams,
+    ReadResourceResult, ServerCapabilities, SetLevelRequestParams, SubscribeRequestParams, Implementation, ErrorData, InitializedNotificationParams,
 };
-use anyhow::Result; // Keep Result
+use anyhow::{bail, Result}; // Keep Result
 use serde_json::Value;
 use tracing::{debug, info, warn};
 
@@ -14,135 +22,232 @@ pub fn handle_initialize(
     params: InitializeRequestParams,
     server_capabilities: &ServerCapabilities, // Pass capabilities
     server_info: &Implementation,         // Pass server info
+    instructions: Option<String>,
+    session: &Session,
 ) -> Result<InitializeResult> {
     info!(
         "handling initialize request: client={:?}, version={}",
         params.client_info, params.protocol_version
     );
 
-    // Basic version check (could be more sophisticated)
-    if params.protocol_version != SUPPORTED_PROTOCOL_VERSION {
-        warn!(
-            "client requested protocol version {}, but server uses {}",
-            params.protocol_version, SUPPORTED_PROTOCOL_VERSION
-        );
-        // Respond with server's version regardless for now
-    }
-
-    // TODO: Store/use client capabilities (params.capabilities) if needed
+    let protocol_version = negotiate_protocol_version(&params.protocol_version);
+    session.set_protocol_version(protocol_version);
+    debug!("client capabilities: {:?}", params.capabilities);
+    session.set_client(params.client_info, params.capabilities);
 
     let result = InitializeResult {
-        protocol_version: SUPPORTED_PROTOCOL_VERSION.to_string(),
+        protocol_version: protocol_version.to_string(),
         capabilities: server_capabilities.clone(), // Use passed capabilities
         server_info: server_info.clone(),         // Use passed server info
-        instructions: None, // No specific instructions for now
+        instructions,
     };
 
     Ok(result)
 }
 
+/// Picks the version to answer `initialize` with: the client's if we support it,
+/// otherwise our latest, leaving it to the client to disconnect if it can't cope.
+pub fn negotiate_protocol_version(requested: &str) -> ProtocolVersion {
+    match ProtocolVersion::parse(requested) {
+        Some(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => {
+            debug!("agreed on client's protocol version {}", version);
+            version
+        }
+        _ => {
+            warn!(
+                "client requested unsupported protocol version {}, offering {}",
+                requested, LATEST_PROTOCOL_VERSION
+            );
+            LATEST_PROTOCOL_VERSION
+        }
+    }
+}
+
 // --- Initialized Notification Handler ---
 // This is a notification, so it doesn't return a result to send back.
 // It might trigger internal state changes.
-pub fn handle_initialized(_params: InitializedNotificationParams) -> Result<()> {
-     info!("received 'initialized' notification from client. connection ready.");
-     // Add any logic needed after initialization confirmation here
+pub fn handle_initialized(_params: InitializedNotificationParams, session: &Session) -> Result<()> {
+     match session.mark_ready() {
+         Ok(()) => info!("received 'initialized' notification from client. connection ready."),
+         Err(state) => warn!("ignoring 'initialized' notification received while {}", state),
+     }
      Ok(())
 }
 
 
+// --- Ping Handler ---
+
+pub fn handle_ping() -> Result<EmptyResult> {
+    debug!("handling ping request");
+    Ok(EmptyResult {})
+}
+
+// --- Logging Handler ---
+
+pub fn handle_set_level(session: &Session, params: SetLevelRequestParams) -> Result<EmptyResult> {
+    info!("handling logging/setLevel request: {:?}", params.level);
+    session.set_log_level(params.level);
+    Ok(EmptyResult {})
+}
+
+// --- Cancellation Notification Handler ---
+
+pub fn handle_cancelled(session: &Session, params: CancelledNotificationParams) -> Result<()> {
+    info!(
+        "client cancelled request {} (reason: {})",
+        params.request_id,
+        params.reason.as_deref().unwrap_or("none given")
+    );
+    if !session.cancel_request(&params.request_id) {
+        // Responses may cross the cancellation on the wire; that's expected
+        debug!("request {} is not in flight, ignoring cancellation", params.request_id);
+    }
+    Ok(())
+}
+
 // --- List Handlers ---
 
-pub fn handle_list_tools() -> Result<ListToolsResult> {
-    info!("handling tools/list request");
-
-    // --- Create a dummy tool ---
-    let dummy_tool = Tool {
-        name: "dummy_tool_from_rust".to_string(),
-        description: Some("A simple test tool.".to_string()),
-        input_schema: serde_json::json!({
-            "type": "object",
-            "properties": {} // No specific input properties for this dummy tool
-        }),
-    };
-    // --- End dummy tool ---
+pub fn handle_list_tools(
+    tools: &ToolRegistry,
+    params: PaginatedRequestParams,
+    page_size: usize,
+) -> Result<ListToolsResult> {
+    info!("handling tools/list request, cursor={:?}", params.cursor);
+    let (tools, next_cursor) = pagination::paginate(tools.list(), params.cursor.as_deref(), page_size)?;
+    let result = ListToolsResult { tools, next_cursor };
+    Ok(result)
+}
 
-    let result = ListToolsResult {
-        tools: vec![dummy_tool], // Send the list with the dummy tool
-    };
+pub async fn handle_list_resources(
+    resources: &ResourceRegistry,
+    params: PaginatedRequestParams,
+    page_size: usize,
+) -> Result<ListResourcesResult> {
+    info!("handling resources/list request, cursor={:?}", params.cursor);
+    resources.list_page(params.cursor.as_deref(), page_size).await // Providers page lazily
+}
+
+pub fn handle_list_prompts(
+    prompts: &PromptRegistry,
+    params: PaginatedRequestParams,
+    page_size: usize,
+) -> Result<ListPromptsResult> {
+    info!("handling prompts/list request, cursor={:?}", params.cursor);
+    let (prompts, next_cursor) = pagination::paginate(prompts.list(), params.cursor.as_deref(), page_size)?;
+    let result = ListPromptsResult { prompts, next_cursor };
     Ok(result)
 }
 
-pub fn handle_list_resources() -> Result<ListResourcesResult> {
-    info!("handling resources/list request");
-    let dummy_resource = Resource {
-        uri: "mcp://dummy/resource/1".to_string(),
-        name: "Dummy Resource".to_string(),
-        description: Some("A test resource from Rust".to_string()),
-    };
-    let result = ListResourcesResult {
-        resources: vec![dummy_resource], // Send dummy
+pub async fn handle_list_resource_templates(
+    resources: &ResourceRegistry,
+    params: PaginatedRequestParams,
+    page_size: usize,
+) -> Result<ListResourceTemplatesResult> {
+    info!("handling resources/templates/list request, cursor={:?}", params.cursor);
+    let (resource_templates, next_cursor) =
+        pagination::paginate(resources.list_templates().await?, params.cursor.as_deref(), page_size)?;
+    let result = ListResourceTemplatesResult {
+        resource_templates,
+        next_cursor,
     };
     Ok(result)
 }
 
-pub fn handle_list_prompts() -> Result<ListPromptsResult> {
-    info!("handling prompts/list request");
-    let dummy_prompt = Prompt {
-        name: "dummy_prompt".to_string(),
-        description: Some("A test prompt from Rust".to_string()),
-        arguments: None, // No args for simplicity
-    };
-    let result = ListPromptsResult {
-        prompts: vec![dummy_prompt], // Send dummy
-    };
+// --- Resource Read Handler ---
+
+/// Returns `Ok(None)` when no provider serves the requested URI.
+pub async fn handle_read_resource(
+    resources: &ResourceRegistry,
+    params: ReadResourceRequestParams,
+) -> Result<Option<ReadResourceResult>> {
+    info!("handling resources/read request for uri: {}", params.uri);
+    let result = resources
+        .read(&params.uri)
+        .await?
+        .map(|contents| ReadResourceResult { contents });
+    Ok(result)
+}
+
+// --- Resource Subscription Handlers ---
+
+pub fn handle_subscribe(session: &Session, params: SubscribeRequestParams) -> Result<EmptyResult> {
+    info!("handling resources/subscribe request for uri: {}", params.uri);
+    if !session.subscribe(&params.uri) {
+        debug!("client was already subscribed to {}", params.uri);
+    }
+    Ok(EmptyResult {})
+}
+
+pub fn handle_unsubscribe(session: &Session, params: SubscribeRequestParams) -> Result<EmptyResult> {
+    info!("handling resources/unsubscribe request for uri: {}", params.uri);
+    if !session.unsubscribe(&params.uri) {
+        debug!("client was not subscribed to {}", params.uri);
+    }
+    Ok(EmptyResult {})
+}
+
+// --- Prompt Get Handler ---
+
+/// Renders a prompt. Required arguments must already have been checked by the caller.
+pub async fn handle_get_prompt(
+    provider: &dyn PromptProvider,
+    params: GetPromptRequestParams,
+) -> Result<GetPromptResult> {
+    info!("handling prompts/get request for prompt: {}", params.name);
+    debug!("prompt arguments: {:?}", params.arguments);
+
+    let result = provider.get(params.arguments.unwrap_or_default()).await?;
     Ok(result)
 }
 
 // --- Tool Call Handler ---
 
-pub fn handle_call_tool(params: CallToolRequestParams) -> Result<CallToolResult> {
+pub async fn handle_call_tool(
+    tool: &RegisteredTool,
+    params: CallToolRequestParams,
+    ctx: RequestContext,
+) -> Result<CallToolResult> {
     info!("handling tools/call request for tool: {}", params.name);
     debug!("tool call arguments: {:?}", params.arguments);
 
-    // Check which tool is being called
-    if params.name == "dummy_tool_from_rust" {
-        // --- Execute Dummy Tool Logic ---
-        info!(
-            "executing dummy_tool_from_rust with args: {:?}",
-            params.arguments
-        );
+    // Omitted arguments are checked (and passed on) as an empty object
+    let arguments = params.arguments.unwrap_or_else(|| Value::Object(Default::default()));
+    let violations = tool.validate_arguments(&arguments)?;
+    if !violations.is_empty() {
+        warn!("rejecting call to tool '{}' with {} invalid arguments", params.name, violations.len());
+        return Err(InvalidArguments {
+            tool: params.name,
+            violations,
+        }
+        .into());
+    }
 
-        // Create a simple success result
-        let result_content = ContentPart {
-            type_: "text".to_string(),
-            text: Some(format!(
-                "dummy_tool_from_rust executed successfully by Rust! Received args: {}",
-                params.arguments
-            )),
-        };
-        let tool_result = CallToolResult {
-            content: vec![result_content],
-            is_error: None, // Indicate success
-        };
-        Ok(tool_result)
-        // --- End Dummy Tool Logic ---
-    } else {
-        // Handle calls to unknown tools by returning an error *within* the result structure
-        warn!("received call for unknown tool: {}", params.name);
-        let error_content = ContentPart {
-            type_: "text".to_string(),
-            text: Some(format!(
-                "Error: Tool '{}' not implemented by this server.",
-                params.name
-            )),
-        };
-        let tool_result = CallToolResult {
-            content: vec![error_content],
-            is_error: Some(true), // Indicate tool execution error
-        };
-        Ok(tool_result) // Still Ok from the handler's perspective, error is in the result
+    let result = tool.handler().call(arguments, ctx).await?;
+    if result.is_error == Some(true) {
+        warn!("tool '{}' reported an execution error", params.name);
+        return Ok(result); // Error results needn't match the output schema
     }
+    if tool.tool().output_schema.is_some() {
+        check_structured_content(tool, result.structured_content.as_ref())?;
+    }
+    Ok(result)
+}
+
+// A tool with an output schema promises clients structured content matching it
+fn check_structured_content(tool: &RegisteredTool, structured_content: Option<&Value>) -> Result<()> {
+    let tool_name = &tool.tool().name;
+    let Some(structured_content) = structured_content else {
+        bail!("tool '{}' declares an output schema but returned no structured content", tool_name);
+    };
+    let violations = tool.validate_output(structured_content)?;
+    if !violations.is_empty() {
+        bail!(
+            "tool '{}' returned structured content not matching its output schema: {}",
+            tool_name,
+            schema::describe(&violations)
+        );
+    }
+    Ok(())
 }
 
 // --- Generic Error Creation ---
@@ -152,7 +257,7 @@ pub fn create_error_response(id: Value, code: i32, message: String) -> crate::ty
     crate::types::GenericErrorResponse {
         jsonrpc: "2.0".to_string(),
         id,
-        error: ErrorData { code, message },
+        error: ErrorData { code, message, data: None },
     }
 }
 
@@ -160,10 +265,58 @@ pub fn method_not_found_error(id: Value, method_name: &str) -> crate::types::Gen
     create_error_response(id, -32601, format!("Method not found: {}", method_name))
 }
 
+pub fn unknown_tool_error(id: Value, tool_name: &str) -> crate::types::GenericErrorResponse {
+    create_error_response(id, -32602, format!("Unknown tool: {}", tool_name))
+}
+
+pub fn unknown_prompt_error(id: Value, prompt_name: &str) -> crate::types::GenericErrorResponse {
+    create_error_response(id, -32602, format!("Unknown prompt: {}", prompt_name))
+}
+
+pub fn resource_not_found_error(id: Value, uri: &str) -> crate::types::GenericErrorResponse {
+    create_error_response(id, -32002, format!("Resource not found: {}", uri))
+}
+
+pub fn invalid_request_error(id: Value, details: &str) -> crate::types::GenericErrorResponse {
+    create_error_response(id, -32600, format!("Invalid request: {}", details))
+}
+
+/// Error for requests that aren't allowed in the current lifecycle state.
+pub fn lifecycle_error(id: Value, method_name: &str, state: LifecycleState) -> crate::types::GenericErrorResponse {
+    let details = match state {
+        LifecycleState::AwaitingInitialize => format!("server not initialized, {} must follow initialize", method_name),
+        LifecycleState::Initializing => format!("{} sent before notifications/initialized", method_name),
+        LifecycleState::Ready => format!("{} is only valid before the server is ready", method_name),
+        LifecycleState::ShuttingDown => format!("server is shutting down, {} rejected", method_name),
+    };
+    invalid_request_error(id, &details)
+}
+
 pub fn invalid_params_error(id: Value, method_name: &str, details: &str) -> crate::types::GenericErrorResponse {
      create_error_response(id, -32602, format!("Invalid params for {}: {}", method_name, details))
 }
 
+/// Error for a failed `*/list` request: invalid params for a bad cursor, internal otherwise.
+pub fn list_error(id: Value, method_name: &str, error: anyhow::Error) -> crate::types::GenericErrorResponse {
+    match error.downcast_ref::<InvalidCursor>() {
+        Some(invalid) => invalid_params_error(id, method_name, &invalid.to_string()),
+        None => create_error_response(id, -32603, format!("Internal error during {}: {}", method_name, error)),
+    }
+}
+
+/// Error for a failed `tools/call`: invalid params listing every violation for rejected
+/// arguments, internal otherwise.
+pub fn tool_call_error(id: Value, error: anyhow::Error) -> crate::types::GenericErrorResponse {
+    match error.downcast_ref::<InvalidArguments>() {
+        Some(invalid) => {
+            let mut response = invalid_params_error(id, "tools/call", &invalid.to_string());
+            response.error.data = Some(serde_json::json!({ "violations": invalid.violations }));
+            response
+        }
+        None => create_error_response(id, -32603, format!("Internal error during tools/call: {}", error)),
+    }
+}
+
 pub fn parse_error(id: Option<Value>, details: &str) -> crate::types::GenericErrorResponse {
      create_error_response(id.unwrap_or(Value::Null), -32700, format!("Parse error: {}", details))
 }
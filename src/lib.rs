@@ -1,9 +1,36 @@
 // Declare the modules
 pub mod constants;
+pub mod context;
 pub mod handlers;
+pub mod logging;
+pub mod pagination;
+pub mod peer;
+pub mod prompts;
+pub mod registry;
+pub mod resources;
+pub mod schema;
 pub mod server;
+pub mod session;
 pub mod stdio;
+pub mod tools;
+pub mod transport;
 pub mod types;
+pub mod uri_template;
 
 pub use types::{Tool, Resource, Prompt};
-pub use server::run;
+pub use context::{ProgressReporter, RequestContext};
+pub use logging::McpLoggingLayer;
+pub use peer::Peer;
+pub use prompts::{PromptProvider, PromptRegistry};
+pub use registry::Registry;
+pub use resources::{ResourceProvider, ResourceRegistry};
+pub use server::{run, Server, ServerBuilder};
+pub use transport::Transport;
+pub use uri_template::UriTemplate;
+pub use tools::{RegisteredTool, ToolHandler, ToolRegistry, TypedToolHandler};
+/// The `schemars` version typed tool arguments must implement `JsonSchema` from.
+///
+/// The derive macro still looks for a `schemars` crate of its own, so either depend on
+/// `schemars` 1.x directly or point the derive here with
+/// `#[schemars(crate = "test_rust_mcp_sdk::schemars")]`.
+pub use schemars;
@@ -0,0 +1,133 @@
+use crate::peer::Peer;
+use crate::types::{LoggingLevel, LoggingMessageNotificationParams};
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+// Targets whose events are never forwarded: logging from the code that writes
+// to the client would otherwise feed back into itself.
+const SUPPRESSED_TARGETS: &[&str] = &[
+    concat!(env!("CARGO_CRATE_NAME"), "::stdio"),
+    concat!(env!("CARGO_CRATE_NAME"), "::peer"),
+    concat!(env!("CARGO_CRATE_NAME"), "::logging"),
+];
+
+thread_local! {
+    // Guards against re-entering the layer while it is already forwarding an event
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A `tracing` layer that forwards events to the client as `notifications/message`.
+///
+/// Created with [`ServerBuilder::logging_layer`](crate::server::ServerBuilder::logging_layer),
+/// which also advertises the `logging` capability. Only events at or above the level the client picked with `logging/setLevel` are
+/// sent. Until the client picks one, nothing is sent unless a default was set with
+/// [`with_default_level`](Self::with_default_level).
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use test_rust_mcp_sdk::ServerBuilder;
+/// use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+///
+/// let mut builder = ServerBuilder::new();
+/// tracing_subscriber::registry()
+///     .with(builder.logging_layer())
+///     .init();
+/// builder.build().serve().await
+/// # }
+/// ```
+pub struct McpLoggingLayer {
+    peer: Peer,
+    default_level: Option<LoggingLevel>,
+}
+
+impl McpLoggingLayer {
+    pub(crate) fn new(peer: Peer) -> Self {
+        Self {
+            peer,
+            default_level: None,
+        }
+    }
+
+    /// Level to forward at before the client sends `logging/setLevel`.
+    pub fn with_default_level(mut self, level: LoggingLevel) -> Self {
+        self.default_level = Some(level);
+        self
+    }
+}
+
+/// Maps a `tracing` level onto the syslog levels MCP uses.
+pub fn to_logging_level(level: &Level) -> LoggingLevel {
+    match *level {
+        Level::ERROR => LoggingLevel::Error,
+        Level::WARN => LoggingLevel::Warning,
+        Level::INFO => LoggingLevel::Info,
+        _ => LoggingLevel::Debug, // DEBUG and TRACE
+    }
+}
+
+impl<S: Subscriber> Layer<S> for McpLoggingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let Some(min_level) = self.peer.session().log_level().or(self.default_level) else {
+            return;
+        };
+        let level = to_logging_level(metadata.level());
+        if level < min_level || SUPPRESSED_TARGETS.iter().any(|t| metadata.target().starts_with(t)) {
+            return;
+        }
+        if FORWARDING.with(|f| f.replace(true)) {
+            return;
+        }
+
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        let params = LoggingMessageNotificationParams {
+            level,
+            logger: Some(metadata.target().to_string()),
+            data: Value::Object(visitor.fields),
+        };
+        // Nowhere to report a failure from inside a logger; the connection is likely gone
+        if let Ok(params) = serde_json::to_value(params) {
+            let _ = self.peer.notify("notifications/message", Some(params));
+        }
+
+        FORWARDING.with(|f| f.set(false));
+    }
+}
+
+// Collects event fields into a JSON object, e.g. `{"message": "...", "tool": "search"}`
+#[derive(Default)]
+struct JsonVisitor {
+    fields: Map<String, Value>,
+}
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
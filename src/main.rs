@@ -1,15 +1,160 @@
 // Change the use statement to match the crate name from the build error
-use test_rust_mcp_sdk::server::run; // Use the crate name 'test_rust_mcp_sdk'
+use test_rust_mcp_sdk::server::ServerBuilder; // Use the crate name 'test_rust_mcp_sdk'
+use test_rust_mcp_sdk::types::{
+    CallToolResult, ContentPart, GetPromptResult, ListResourcesResult, Prompt, PromptArgument,
+    PromptMessage, Resource, ResourceContents, ResourceTemplate, Role, Tool, ToolAnnotations,
+};
+use test_rust_mcp_sdk::pagination::paginate;
+use test_rust_mcp_sdk::{PromptProvider, RequestContext, ResourceProvider, ToolHandler};
 
 // Keep standard library/external crate imports needed for main
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
 use tracing::{error, info, Level}; // Keep Level
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-use home; // Keep home
+
+// --- Example tool ---
+struct DummyTool;
+
+#[async_trait]
+impl ToolHandler for DummyTool {
+    fn tool(&self) -> Tool {
+        Tool {
+            name: "dummy_tool_from_rust".to_string(),
+            description: Some("A simple test tool.".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {} // No specific input properties for this dummy tool
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn call(&self, arguments: Value, _ctx: RequestContext) -> Result<CallToolResult> {
+        info!("executing dummy_tool_from_rust with args: {:?}", arguments);
+        Ok(CallToolResult {
+            content: vec![ContentPart::text(format!(
+                "dummy_tool_from_rust executed successfully by Rust! Received args: {}",
+                arguments
+            ))],
+            is_error: None, // Indicate success
+            ..Default::default()
+        })
+    }
+}
+
+// --- Example resource provider ---
+struct DummyResources;
+
+const DUMMY_RESOURCE_URI: &str = "mcp://dummy/resource/1";
+
+#[async_trait]
+impl ResourceProvider for DummyResources {
+    async fn list_page(&self, cursor: Option<String>, limit: usize) -> Result<ListResourcesResult> {
+        let resources = vec![Resource {
+            uri: DUMMY_RESOURCE_URI.to_string(),
+            name: "Dummy Resource".to_string(),
+            description: Some("A test resource from Rust".to_string()),
+            mime_type: Some("text/plain".to_string()),
+        }];
+        let (resources, next_cursor) = paginate(resources, cursor.as_deref(), limit)?;
+        Ok(ListResourcesResult { resources, next_cursor })
+    }
+
+    async fn read(&self, uri: &str) -> Result<Option<Vec<ResourceContents>>> {
+        if uri != DUMMY_RESOURCE_URI {
+            return Ok(None);
+        }
+        Ok(Some(vec![ResourceContents::text(
+            uri,
+            Some("text/plain".to_string()),
+            "Hello from a Rust resource!",
+        )]))
+    }
+
+    async fn list_templates(&self) -> Result<Vec<ResourceTemplate>> {
+        Ok(vec![ResourceTemplate {
+            uri_template: "mcp://dummy/greeting/{name}".to_string(),
+            name: "Greeting".to_string(),
+            description: Some("A personalised greeting".to_string()),
+            mime_type: Some("text/plain".to_string()),
+        }])
+    }
+
+    async fn read_template(
+        &self,
+        uri: &str,
+        _template: &ResourceTemplate,
+        variables: HashMap<String, String>,
+    ) -> Result<Option<Vec<ResourceContents>>> {
+        let name = variables.get("name").map(String::as_str).unwrap_or("stranger");
+        Ok(Some(vec![ResourceContents::text(
+            uri,
+            Some("text/plain".to_string()),
+            format!("Hello, {}!", name),
+        )]))
+    }
+}
+
+// --- Example prompt ---
+struct DummyPrompt;
+
+#[async_trait]
+impl PromptProvider for DummyPrompt {
+    fn prompt(&self) -> Prompt {
+        Prompt {
+            name: "dummy_prompt".to_string(),
+            description: Some("A test prompt from Rust".to_string()),
+            arguments: Some(vec![PromptArgument {
+                name: "topic".to_string(),
+                description: Some("What to ask about".to_string()),
+                required: true,
+            }]),
+        }
+    }
+
+    async fn get(&self, arguments: HashMap<String, String>) -> Result<GetPromptResult> {
+        let topic = &arguments["topic"]; // Required, so always present
+        Ok(GetPromptResult {
+            description: Some(format!("Questions about {}", topic)),
+            messages: vec![
+                PromptMessage {
+                    role: Role::User,
+                    content: ContentPart::text(format!("Tell me something about {}.", topic)),
+                },
+                PromptMessage {
+                    role: Role::User,
+                    content: ContentPart::resource(ResourceContents::text(
+                        DUMMY_RESOURCE_URI,
+                        Some("text/plain".to_string()),
+                        "Hello from a Rust resource!",
+                    )),
+                },
+            ],
+        })
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Configured first so the tracing setup below can forward logs to the client
+    let mut builder = ServerBuilder::new()
+        .server_info("rust-mcp-stdio-refactored", "0.1.1")
+        .annotated_tool(
+            DummyTool,
+            Some("Dummy Tool".to_string()),
+            ToolAnnotations {
+                read_only_hint: Some(true),
+                open_world_hint: Some(false),
+                ..Default::default()
+            },
+        )
+        .resource_provider(DummyResources)
+        .prompt(DummyPrompt);
+
     // --- Tracing setup ---
     let home_dir = home::home_dir().context("failed to get user home directory")?;
     let log_directory = home_dir.join(".screenpipe").join("logs").join("rust_stdio_refactored_logs"); // Log dir name change
@@ -30,6 +175,7 @@ async fn main() -> Result<()> {
                 .with_span_events(FmtSpan::NONE) // Don't include span events
                 .with_writer(non_blocking_writer), // Write to the non-blocking file appender
         )
+        .with(builder.logging_layer()) // Forward logs to the client once it calls logging/setLevel
         // Optionally, add another layer for console logging if desired during development
         // .with(
         //     tracing_subscriber::fmt::layer()
@@ -40,8 +186,9 @@ async fn main() -> Result<()> {
 
     info!("starting mcp rust stdio server process...");
 
-    // Call the imported run function directly
-    if let Err(e) = run().await {
+    let server = builder.build();
+
+    if let Err(e) = server.serve().await {
         error!("server exited with error: {:?}", e);
         // Consider exiting with a non-zero status code on error
         std::process::exit(1);
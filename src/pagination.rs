@@ -0,0 +1,128 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Items per page for `*/list` requests unless configured with
+/// [`ServerBuilder::page_size`](crate::server::ServerBuilder::page_size).
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Returned (wrapped in `anyhow::Error`) for a cursor the server did not hand out.
+///
+/// Reported to the client as invalid params (-32602), as the spec asks.
+#[derive(Debug, Clone)]
+pub struct InvalidCursor(pub String);
+
+impl std::fmt::Display for InvalidCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid cursor: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidCursor {}
+
+// Position in a list, handed to the client as an opaque base64 string
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub(crate) struct Cursor {
+    #[serde(rename = "i")]
+    pub index: usize, // Offset into a list, or the provider to resume at
+    #[serde(rename = "c", skip_serializing_if = "Option::is_none")]
+    pub inner: Option<String>, // That provider's own cursor
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serializes");
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// Decodes a client-supplied cursor; `None` means the first page.
+    pub fn decode(cursor: Option<&str>) -> Result<Self, InvalidCursor> {
+        let Some(cursor) = cursor else {
+            return Ok(Self::default());
+        };
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| InvalidCursor(cursor.to_string()))
+    }
+}
+
+/// Cuts one page out of a fully materialised list.
+///
+/// Returns the page and the cursor for the next one, if there is more.
+pub fn paginate<T>(items: Vec<T>, cursor: Option<&str>, page_size: usize) -> Result<(Vec<T>, Option<String>), InvalidCursor> {
+    let start = Cursor::decode(cursor)?.index;
+    if start > items.len() {
+        return Err(InvalidCursor(cursor.unwrap_or_default().to_string()));
+    }
+    let end = start.saturating_add(page_size.max(1)).min(items.len());
+    let next_cursor = (end < items.len()).then(|| Cursor { index: end, inner: None }.encode());
+    let page = items.into_iter().skip(start).take(end - start).collect();
+    Ok((page, next_cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor {
+            index: 3,
+            inner: Some("provider-cursor".to_string()),
+        };
+        assert_eq!(Cursor::decode(Some(&cursor.encode())).unwrap(), cursor);
+
+        let cursor = Cursor { index: 7, inner: None };
+        assert_eq!(Cursor::decode(Some(&cursor.encode())).unwrap(), cursor);
+    }
+
+    #[test]
+    fn no_cursor_is_the_first_page() {
+        assert_eq!(Cursor::decode(None).unwrap(), Cursor::default());
+    }
+
+    #[test]
+    fn invalid_cursors_are_rejected() {
+        assert!(Cursor::decode(Some("not base64!")).is_err());
+        let not_json = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("nope");
+        assert!(Cursor::decode(Some(&not_json)).is_err());
+        let wrong_shape = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"i":-1}"#);
+        assert!(Cursor::decode(Some(&wrong_shape)).is_err());
+    }
+
+    #[test]
+    fn paginate_walks_every_page() {
+        let (page, next) = paginate((0..5).collect(), None, 2).unwrap();
+        assert_eq!(page, vec![0, 1]);
+        let (page, next) = paginate((0..5).collect(), next.as_deref(), 2).unwrap();
+        assert_eq!(page, vec![2, 3]);
+        let (page, next) = paginate((0..5).collect(), next.as_deref(), 2).unwrap();
+        assert_eq!(page, vec![4]);
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn paginate_stops_at_an_exact_boundary() {
+        let (page, next) = paginate((0..4).collect(), None, 2).unwrap();
+        assert_eq!(page, vec![0, 1]);
+        let (page, next) = paginate((0..4).collect(), next.as_deref(), 2).unwrap();
+        assert_eq!(page, vec![2, 3]);
+        assert_eq!(next, None);
+
+        let (page, next) = paginate(Vec::<u32>::new(), None, 2).unwrap();
+        assert!(page.is_empty());
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn paginate_rejects_out_of_range_cursors() {
+        let past_end = Cursor { index: 6, inner: None }.encode();
+        assert!(paginate((0..5).collect::<Vec<_>>(), Some(&past_end), 2).is_err());
+
+        let at_end = Cursor { index: 5, inner: None }.encode();
+        let (page, next) = paginate((0..5).collect::<Vec<_>>(), Some(&at_end), 2).unwrap();
+        assert!(page.is_empty());
+        assert_eq!(next, None);
+    }
+}
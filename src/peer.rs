@@ -0,0 +1,263 @@
+use crate::session::{LifecycleState, Session};
+use crate::types::{
+    CancelledNotificationParams, ErrorData, GenericIncomingResponse, GenericNotification,
+    GenericOutgoingRequest, ListRootsResult, ResourceUpdatedNotificationParams, Root,
+};
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, trace, warn};
+
+/// How long [`Peer::request`] waits for the client unless configured otherwise.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+type ResponseSender = oneshot::Sender<Result<Value, ErrorData>>;
+
+// Requests sent to the client that are still waiting for a response
+struct PendingRequests {
+    next_id: AtomicU64,
+    waiting: Mutex<HashMap<String, ResponseSender>>, // Keyed by serialized request id
+    timeout_ms: AtomicU64,
+}
+
+/// Handle to the connected client, used to send messages outside of a request/response cycle.
+///
+/// Cheap to clone; every clone feeds the same writer task, which owns the transport.
+#[derive(Clone)]
+pub struct Peer {
+    outbound: mpsc::UnboundedSender<Value>,
+    session: Arc<Session>,
+    pending: Arc<PendingRequests>,
+}
+
+impl Peer {
+    pub(crate) fn new(outbound: mpsc::UnboundedSender<Value>, session: Arc<Session>) -> Self {
+        Self {
+            outbound,
+            session,
+            pending: Arc::new(PendingRequests {
+                next_id: AtomicU64::new(1),
+                waiting: Mutex::new(HashMap::new()),
+                timeout_ms: AtomicU64::new(DEFAULT_REQUEST_TIMEOUT.as_millis() as u64),
+            }),
+        }
+    }
+
+    pub fn session(&self) -> &Arc<Session> {
+        &self.session
+    }
+
+    /// Queues any JSON-RPC message for the writer task.
+    pub(crate) fn send(&self, message: &impl Serialize) -> Result<()> {
+        let value = serde_json::to_value(message)?;
+        self.outbound
+            .send(value)
+            .map_err(|_| anyhow!("connection closed, message dropped"))
+    }
+
+    /// Whether the writer task has gone away, e.g. after a write error.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.outbound.is_closed()
+    }
+
+    /// Sends a notification with the given method and params.
+    pub fn notify(&self, method: &str, params: Option<Value>) -> Result<()> {
+        trace!("queueing notification: {}", method);
+        self.send(&GenericNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+        })
+    }
+
+    /// Sends `notifications/resources/updated` if the client subscribed to `uri`.
+    ///
+    /// Returns whether a notification was sent.
+    pub fn notify_resource_updated(&self, uri: &str) -> Result<bool> {
+        if !self.session.is_subscribed(uri) {
+            debug!("skipping update for unsubscribed resource: {}", uri);
+            return Ok(false);
+        }
+        let params = ResourceUpdatedNotificationParams { uri: uri.to_string() };
+        self.notify("notifications/resources/updated", Some(serde_json::to_value(params)?))?;
+        Ok(true)
+    }
+
+    /// Sends `notifications/tools/list_changed`. Returns `false` if skipped because the
+    /// client has not finished initializing.
+    pub fn notify_tools_list_changed(&self) -> Result<bool> {
+        self.notify_list_changed("notifications/tools/list_changed")
+    }
+
+    /// Sends `notifications/prompts/list_changed`. Returns `false` if skipped because the
+    /// client has not finished initializing.
+    pub fn notify_prompts_list_changed(&self) -> Result<bool> {
+        self.notify_list_changed("notifications/prompts/list_changed")
+    }
+
+    /// Sends `notifications/resources/list_changed`. Returns `false` if skipped because the
+    /// client has not finished initializing.
+    pub fn notify_resources_list_changed(&self) -> Result<bool> {
+        self.notify_list_changed("notifications/resources/list_changed")
+    }
+
+    // Skipped before the handshake completes: the client lists everything once ready anyway
+    pub(crate) fn notify_list_changed(&self, method: &str) -> Result<bool> {
+        if self.session.lifecycle() != LifecycleState::Ready {
+            debug!("skipping {} while {}", method, self.session.lifecycle());
+            return Ok(false);
+        }
+        self.notify(method, None)?;
+        Ok(true)
+    }
+
+    // --- Server-to-Client Requests ---
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_millis(self.pending.timeout_ms.load(Ordering::Relaxed))
+    }
+
+    /// Changes the timeout used by [`request`](Self::request) for every clone of this peer.
+    pub fn set_request_timeout(&self, timeout: Duration) {
+        self.pending
+            .timeout_ms
+            .store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Sends a request to the client and waits for its result, using the default timeout.
+    ///
+    /// An error response from the client comes back as an `anyhow::Error` wrapping
+    /// [`ErrorData`], so its code can be inspected with `downcast_ref`.
+    pub async fn request(&self, method: &str, params: Option<Value>) -> Result<Value> {
+        self.request_with_timeout(method, params, self.request_timeout()).await
+    }
+
+    /// Like [`request`](Self::request), with an explicit timeout. On timeout the client is
+    /// told to stop working on the request via `notifications/cancelled`.
+    pub async fn request_with_timeout(
+        &self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Value> {
+        // The spec only allows pings before the client has confirmed initialization
+        let state = self.session.lifecycle();
+        if state != LifecycleState::Ready && method != "ping" {
+            bail!("cannot send {} to the client while {}", method, state);
+        }
+
+        let id = Value::from(self.pending.next_id.fetch_add(1, Ordering::Relaxed));
+        let (response_tx, response_rx) = oneshot::channel();
+        self.pending
+            .waiting
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), response_tx);
+        // Cleans up if this future is dropped early, e.g. when the calling handler is cancelled
+        let mut guard = PendingGuard {
+            peer: self,
+            id: id.clone(),
+            armed: true,
+        };
+
+        debug!("sending request to client: id={}, method={}", id, method);
+        let request = GenericOutgoingRequest {
+            jsonrpc: "2.0".to_string(),
+            id: id.clone(),
+            method: method.to_string(),
+            params,
+        };
+        if let Err(e) = self.send(&request) {
+            guard.armed = false;
+            self.pending.waiting.lock().unwrap().remove(&id.to_string());
+            return Err(e);
+        }
+
+        let outcome = tokio::time::timeout(timeout, response_rx).await;
+        guard.armed = false;
+        match outcome {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(error))) => Err(error.into()),
+            Ok(Err(_)) => bail!("connection closed before the client answered {}", method),
+            Err(_) => {
+                warn!("client did not answer {} (id {}) within {:?}", method, id, timeout);
+                self.abandon_request(&id, "timed out");
+                bail!("request {} timed out after {:?}", method, timeout)
+            }
+        }
+    }
+
+    /// Fetches the client's roots with `roots/list` and caches them in the session.
+    ///
+    /// Asks again if the client announces a change before answering, so the result is
+    /// never older than the last `notifications/roots/list_changed`.
+    pub async fn list_roots(&self) -> Result<Vec<Root>> {
+        if !self.session.client_capabilities().supports_roots() {
+            bail!("client does not support roots");
+        }
+        loop {
+            let generation = self.session.roots_generation();
+            let result: ListRootsResult = serde_json::from_value(self.request("roots/list", None).await?)?;
+            if self.session.set_roots(result.roots.clone(), generation) {
+                debug!("client roots: {:?}", result.roots);
+                return Ok(result.roots);
+            }
+            debug!("client roots changed while fetching them, asking again");
+        }
+    }
+
+    // Forgets a request and tells the client it can stop working on it
+    fn abandon_request(&self, id: &Value, reason: &str) {
+        self.pending.waiting.lock().unwrap().remove(&id.to_string());
+        let cancel = CancelledNotificationParams {
+            request_id: id.clone(),
+            reason: Some(reason.to_string()),
+        };
+        let _ = self.notify("notifications/cancelled", serde_json::to_value(cancel).ok());
+    }
+
+    /// Routes a response from the client to the `request` call awaiting it.
+    pub(crate) fn handle_response(&self, response: GenericIncomingResponse) {
+        let Some(waiter) = self.pending.waiting.lock().unwrap().remove(&response.id.to_string()) else {
+            warn!("received response for unknown or expired request id {}", response.id);
+            return;
+        };
+        let outcome = match (response.result, response.error) {
+            (_, Some(error)) => Err(error),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(Value::Null), // Malformed, but nothing better to hand back
+        };
+        // The caller may have given up already; nothing to do then
+        let _ = waiter.send(outcome);
+    }
+
+    /// Fails every request still waiting for the client, e.g. once the transport closed.
+    pub(crate) fn fail_pending_requests(&self) {
+        let drained: Vec<_> = self.pending.waiting.lock().unwrap().drain().collect();
+        if !drained.is_empty() {
+            debug!("dropping {} requests still awaiting the client", drained.len());
+        }
+        // Dropping the senders wakes the waiters with a "connection closed" error
+    }
+}
+
+// Abandons an outgoing request whose `request` future was dropped before completion
+struct PendingGuard<'a> {
+    peer: &'a Peer,
+    id: Value,
+    armed: bool,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            debug!("request {} to client was abandoned before completion", self.id);
+            self.peer.abandon_request(&self.id, "request abandoned by server");
+        }
+    }
+}
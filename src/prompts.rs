@@ -0,0 +1,53 @@
+use crate::registry::{Registry, RegistryItem};
+use crate::types::{GetPromptResult, Prompt};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// --- Prompt Provider Trait ---
+
+/// Implemented by anything that can be exposed to clients as an MCP prompt.
+#[async_trait]
+pub trait PromptProvider: Send + Sync {
+    /// The prompt definition advertised in `prompts/list`.
+    fn prompt(&self) -> Prompt;
+
+    /// Renders the prompt messages.
+    ///
+    /// Arguments declared `required` in [`prompt`](Self::prompt) are checked before this is
+    /// called, so they can be looked up without handling the missing case.
+    async fn get(&self, arguments: HashMap<String, String>) -> Result<GetPromptResult>;
+}
+
+/// Names of the arguments `prompt` declares as required but `arguments` lacks.
+pub fn missing_arguments(prompt: &Prompt, arguments: &HashMap<String, String>) -> Vec<String> {
+    prompt
+        .arguments
+        .iter()
+        .flatten()
+        .filter(|arg| arg.required && !arguments.contains_key(&arg.name))
+        .map(|arg| arg.name.clone())
+        .collect()
+}
+
+// --- Prompt Registry ---
+
+/// Holds the prompts served by this server, keyed by name; see [`Registry`].
+pub type PromptRegistry = Registry<dyn PromptProvider>;
+
+impl RegistryItem for dyn PromptProvider {
+    const LIST_CHANGED: &'static str = "notifications/prompts/list_changed";
+}
+
+impl Registry<dyn PromptProvider> {
+    /// Registers a prompt. A prompt registered under an existing name replaces the old one.
+    pub fn register(&self, provider: impl PromptProvider + 'static) {
+        self.insert(provider.prompt().name, Arc::new(provider));
+    }
+
+    /// Prompt definitions in registration order.
+    pub fn list(&self) -> Vec<Prompt> {
+        self.items().iter().map(|provider| provider.prompt()).collect()
+    }
+}
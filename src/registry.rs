@@ -0,0 +1,113 @@
+use crate::peer::Peer;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::warn;
+
+/// Something a [`Registry`] can hold: tools, prompts or resource providers.
+pub trait RegistryItem: Send + Sync {
+    /// Notification sent to the client when items are added or removed.
+    const LIST_CHANGED: &'static str;
+}
+
+/// Named items served by this server, kept in registration order.
+///
+/// Clones share the same items, so a clone kept after handing the registry to the
+/// server can add or remove items while it is serving. If the server advertised the
+/// matching capability, the client is then sent [`RegistryItem::LIST_CHANGED`].
+pub struct Registry<T: ?Sized> {
+    inner: Arc<RwLock<RegistryInner<T>>>,
+}
+
+struct RegistryInner<T: ?Sized> {
+    items: HashMap<String, Arc<T>>,
+    order: Vec<String>, // Registration order, so */list is stable
+    peer: Option<Peer>, // Set by the server when it advertises this registry
+}
+
+impl<T: ?Sized> Clone for Registry<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: ?Sized> Default for Registry<T> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(RegistryInner {
+                items: HashMap::new(),
+                order: Vec::new(),
+                peer: None,
+            })),
+        }
+    }
+}
+
+impl<T: ?Sized + RegistryItem> Registry<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an item. An item added under an existing name replaces the old one.
+    pub(crate) fn insert(&self, name: String, item: Arc<T>) {
+        let peer = {
+            let mut inner = self.inner.write().unwrap();
+            if inner.items.insert(name.clone(), item).is_none() {
+                inner.order.push(name);
+            }
+            inner.peer.clone()
+        };
+        notify_list_changed::<T>(peer);
+    }
+
+    /// Removes an item. Returns `false` if nothing was registered under that name.
+    pub fn remove(&self, name: &str) -> bool {
+        let peer = {
+            let mut inner = self.inner.write().unwrap();
+            if inner.items.remove(name).is_none() {
+                return false;
+            }
+            inner.order.retain(|n| n != name);
+            inner.peer.clone()
+        };
+        notify_list_changed::<T>(peer);
+        true
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<T>> {
+        self.inner.read().unwrap().items.get(name).cloned()
+    }
+
+    /// All items in registration order, as a snapshot that holds no lock.
+    pub fn items(&self) -> Vec<Arc<T>> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .order
+            .iter()
+            .filter_map(|name| inner.items.get(name).cloned())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.read().unwrap().items.is_empty()
+    }
+
+    /// Announces later changes to the client. Only called for registries whose
+    /// capability the server advertised, since clients ignore the rest.
+    pub(crate) fn attach(&self, peer: Peer) {
+        self.inner.write().unwrap().peer = Some(peer);
+    }
+}
+
+fn notify_list_changed<T: ?Sized + RegistryItem>(peer: Option<Peer>) {
+    if let Some(peer) = peer {
+        if let Err(e) = peer.notify_list_changed(T::LIST_CHANGED) {
+            warn!("failed to send {}: {:?}", T::LIST_CHANGED, e);
+        }
+    }
+}
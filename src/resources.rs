@@ -0,0 +1,281 @@
+use crate::pagination::{self, Cursor, InvalidCursor};
+use crate::registry::{Registry, RegistryItem};
+use crate::types::{ListResourcesResult, Resource, ResourceContents, ResourceTemplate};
+use crate::uri_template::UriTemplate;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+// --- Resource Provider Trait ---
+
+/// A source of resources, e.g. a directory, a database or a fixed set of documents.
+#[async_trait]
+pub trait ResourceProvider: Send + Sync {
+    /// Up to `limit` resources starting at `cursor`, for `resources/list`.
+    ///
+    /// `cursor` is `None` for the first page, and otherwise a `next_cursor` this provider
+    /// returned earlier. Providers holding their resources in memory can cut pages with
+    /// [`paginate`](crate::pagination::paginate); others can page lazily, e.g. with a
+    /// database query, instead of building the full list for every page.
+    async fn list_page(&self, cursor: Option<String>, limit: usize) -> Result<ListResourcesResult>;
+
+    /// Every resource this provider serves, fetched page by page.
+    async fn list(&self) -> Result<Vec<Resource>> {
+        let mut resources = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.list_page(cursor, pagination::DEFAULT_PAGE_SIZE).await?;
+            resources.extend(page.resources);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(resources),
+            }
+        }
+    }
+
+    /// Reads the resource at `uri`.
+    ///
+    /// Returns `Ok(None)` when the URI does not belong to this provider, so the next
+    /// provider gets a chance to serve it.
+    async fn read(&self, uri: &str) -> Result<Option<Vec<ResourceContents>>>;
+
+    /// Resource templates advertised in `resources/templates/list`.
+    async fn list_templates(&self) -> Result<Vec<ResourceTemplate>> {
+        Ok(Vec::new())
+    }
+
+    /// Reads a URI that matched one of this provider's templates.
+    ///
+    /// Only called when [`read`](Self::read) returned `Ok(None)` for the URI. `variables`
+    /// holds the percent-decoded values extracted from it.
+    async fn read_template(
+        &self,
+        _uri: &str,
+        _template: &ResourceTemplate,
+        _variables: HashMap<String, String>,
+    ) -> Result<Option<Vec<ResourceContents>>> {
+        Ok(None)
+    }
+}
+
+// --- Resource Registry ---
+
+/// Holds the resource providers served by this server, queried in registration order.
+///
+/// Providers are keyed by the id [`register`](Registry::register) returns, which
+/// [`remove`](Registry::remove) takes. Providers whose own listing changes should call
+/// [`Peer::notify_resources_list_changed`](crate::peer::Peer::notify_resources_list_changed).
+pub type ResourceRegistry = Registry<dyn ResourceProvider>;
+
+impl RegistryItem for dyn ResourceProvider {
+    const LIST_CHANGED: &'static str = "notifications/resources/list_changed";
+}
+
+// Providers have no name of their own, so each gets a unique id
+static NEXT_PROVIDER_ID: AtomicUsize = AtomicUsize::new(0);
+
+impl Registry<dyn ResourceProvider> {
+    /// Registers a provider, returning the id to [`remove`](Registry::remove) it with.
+    pub fn register(&self, provider: impl ResourceProvider + 'static) -> String {
+        let id = format!("provider-{}", NEXT_PROVIDER_ID.fetch_add(1, Ordering::Relaxed));
+        self.insert(id.clone(), Arc::new(provider));
+        id
+    }
+
+    /// Resources from every provider, concatenated.
+    pub async fn list(&self) -> Result<Vec<Resource>> {
+        let providers = self.items();
+        let mut resources = Vec::new();
+        for provider in &providers {
+            resources.extend(provider.list().await?);
+        }
+        Ok(resources)
+    }
+
+    /// One page of resources across every provider, for `resources/list`.
+    ///
+    /// Fails with [`InvalidCursor`] if `cursor` was not handed out by this registry.
+    pub async fn list_page(&self, cursor: Option<&str>, page_size: usize) -> Result<ListResourcesResult> {
+        let providers = self.items();
+        let Cursor { mut index, inner: mut provider_cursor } = Cursor::decode(cursor)?;
+        if index > providers.len() {
+            return Err(InvalidCursor(cursor.unwrap_or_default().to_string()).into());
+        }
+
+        // Fill the page from consecutive providers, resuming where the cursor left off
+        let mut resources = Vec::new();
+        while resources.len() < page_size && index < providers.len() {
+            let page = providers[index]
+                .list_page(provider_cursor.take(), page_size - resources.len())
+                .await?;
+            resources.extend(page.resources);
+            match page.next_cursor {
+                Some(next) => {
+                    // Keep pages short rather than trusting the provider to make progress
+                    provider_cursor = Some(next);
+                    break;
+                }
+                None => index += 1,
+            }
+        }
+
+        let next_cursor = (index < providers.len()).then(|| {
+            Cursor {
+                index,
+                inner: provider_cursor,
+            }
+            .encode()
+        });
+        Ok(ListResourcesResult { resources, next_cursor })
+    }
+
+    /// Resource templates from every provider, concatenated.
+    pub async fn list_templates(&self) -> Result<Vec<ResourceTemplate>> {
+        let providers = self.items();
+        let mut templates = Vec::new();
+        for provider in &providers {
+            templates.extend(provider.list_templates().await?);
+        }
+        Ok(templates)
+    }
+
+    /// Contents from the first provider that claims `uri`, or `None` if none does.
+    ///
+    /// Concrete resources take precedence; after that the URI is matched against each
+    /// provider's templates and handed to the provider owning the first match.
+    pub async fn read(&self, uri: &str) -> Result<Option<Vec<ResourceContents>>> {
+        let providers = self.items();
+        for provider in &providers {
+            if let Some(contents) = provider.read(uri).await? {
+                return Ok(Some(contents));
+            }
+        }
+
+        for provider in &providers {
+            for template in provider.list_templates().await? {
+                let parsed = match UriTemplate::parse(&template.uri_template) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        warn!("skipping invalid resource template '{}': {}", template.name, e);
+                        continue;
+                    }
+                };
+                let Some(variables) = parsed.match_uri(uri) else {
+                    continue;
+                };
+                debug!("uri {} matched template {} with {:?}", uri, parsed, variables);
+                if let Some(contents) = provider.read_template(uri, &template, variables).await? {
+                    return Ok(Some(contents));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Serves `count` resources named `<prefix>0`, `<prefix>1`, ...
+    struct Numbered {
+        prefix: &'static str,
+        count: usize,
+    }
+
+    #[async_trait]
+    impl ResourceProvider for Numbered {
+        async fn list_page(&self, cursor: Option<String>, limit: usize) -> Result<ListResourcesResult> {
+            let resources = (0..self.count)
+                .map(|i| Resource {
+                    uri: format!("test://{}{}", self.prefix, i),
+                    name: format!("{}{}", self.prefix, i),
+                    description: None,
+                    mime_type: None,
+                })
+                .collect();
+            let (resources, next_cursor) = pagination::paginate(resources, cursor.as_deref(), limit)?;
+            Ok(ListResourcesResult { resources, next_cursor })
+        }
+
+        async fn read(&self, _uri: &str) -> Result<Option<Vec<ResourceContents>>> {
+            Ok(None)
+        }
+    }
+
+    fn registry(providers: &[(&'static str, usize)]) -> ResourceRegistry {
+        let registry = ResourceRegistry::new();
+        for &(prefix, count) in providers {
+            registry.register(Numbered { prefix, count });
+        }
+        registry
+    }
+
+    fn names(page: &ListResourcesResult) -> Vec<&str> {
+        page.resources.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn list_walks_a_providers_pages() {
+        let provider = Numbered { prefix: "a", count: pagination::DEFAULT_PAGE_SIZE + 1 };
+        assert_eq!(provider.list().await.unwrap().len(), pagination::DEFAULT_PAGE_SIZE + 1);
+    }
+
+    #[tokio::test]
+    async fn list_page_resumes_partway_through_a_provider() {
+        let registry = registry(&[("a", 3), ("b", 2)]);
+        let page = registry.list_page(None, 2).await.unwrap();
+        assert_eq!(names(&page), ["a0", "a1"]);
+        let page = registry.list_page(page.next_cursor.as_deref(), 2).await.unwrap();
+        assert_eq!(names(&page), ["a2", "b0"]);
+        let page = registry.list_page(page.next_cursor.as_deref(), 2).await.unwrap();
+        assert_eq!(names(&page), ["b1"]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn list_page_moves_on_when_a_provider_ends_on_the_boundary() {
+        let registry = registry(&[("a", 2), ("b", 2)]);
+        let page = registry.list_page(None, 2).await.unwrap();
+        assert_eq!(names(&page), ["a0", "a1"]);
+        let page = registry.list_page(page.next_cursor.as_deref(), 2).await.unwrap();
+        assert_eq!(names(&page), ["b0", "b1"]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn list_page_skips_empty_providers() {
+        let registry = registry(&[("a", 0), ("b", 1), ("c", 0)]);
+        let page = registry.list_page(None, 2).await.unwrap();
+        assert_eq!(names(&page), ["b0"]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn list_page_rejects_invalid_cursors() {
+        let registry = registry(&[("a", 3)]);
+        let error = registry.list_page(Some("garbage"), 2).await.unwrap_err();
+        assert!(error.downcast_ref::<InvalidCursor>().is_some());
+
+        let past_end = Cursor { index: 2, inner: None }.encode();
+        let error = registry.list_page(Some(&past_end), 2).await.unwrap_err();
+        assert!(error.downcast_ref::<InvalidCursor>().is_some());
+
+        // The provider's own cursor is checked by the provider
+        let bad_inner = Cursor { index: 0, inner: Some("garbage".to_string()) }.encode();
+        let error = registry.list_page(Some(&bad_inner), 2).await.unwrap_err();
+        assert!(error.downcast_ref::<InvalidCursor>().is_some());
+    }
+
+    #[tokio::test]
+    async fn list_page_at_the_end_is_empty() {
+        let registry = registry(&[("a", 3)]);
+        let at_end = Cursor { index: 1, inner: None }.encode();
+        let page = registry.list_page(Some(&at_end), 2).await.unwrap();
+        assert!(page.resources.is_empty());
+        assert_eq!(page.next_cursor, None);
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// One way a JSON value fails to match a schema.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaViolation {
+    pub path: String, // JSON pointer into the value, "" for the value itself
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// A JSON Schema compiled once, for checking many values against it.
+pub struct Validator(jsonschema::Validator);
+
+impl Validator {
+    /// Compiles `schema`, failing if it is not a valid JSON Schema.
+    pub fn new(schema: &Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(schema).map_err(|e| anyhow!("invalid JSON Schema: {}", e))?;
+        Ok(Self(validator))
+    }
+
+    /// Checks `instance`, collecting every violation. Empty if `instance` is valid.
+    pub fn validate(&self, instance: &Value) -> Vec<SchemaViolation> {
+        self.0
+            .iter_errors(instance)
+            .map(|error| SchemaViolation {
+                path: error.instance_path.to_string(),
+                message: error.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Checks `instance` against the JSON Schema `schema`, collecting every violation.
+///
+/// Returns an empty list if `instance` is valid. Fails if `schema` itself is not a
+/// valid JSON Schema. Compile a [`Validator`] instead to check many values.
+pub fn validate(schema: &Value, instance: &Value) -> Result<Vec<SchemaViolation>> {
+    Ok(Validator::new(schema)?.validate(instance))
+}
+
+/// Joins violations into a single line, e.g. for an error message.
+pub fn describe(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
@@ -1,43 +1,352 @@
+use crate::constants::LATEST_PROTOCOL_VERSION;
+use crate::context::RequestContext;
+use crate::logging::McpLoggingLayer;
+use crate::pagination::DEFAULT_PAGE_SIZE;
+use crate::peer::Peer;
+use crate::prompts::{self, PromptProvider, PromptRegistry};
+use crate::resources::{ResourceProvider, ResourceRegistry};
+use crate::session::{LifecycleState, Session};
+use crate::tools::{ToolHandler, ToolRegistry, TypedToolHandler};
+use crate::transport::{BoxedWriter, Transport};
 use crate::{handlers, stdio, types}; // Use crate:: for sibling modules
 use anyhow::Result;
 use futures::StreamExt;
 use serde_json::Value;
-use tokio::io::{self, BufReader, Stdout};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinError, JoinSet};
 use tokio_util::codec::{FramedRead, LinesCodec};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, trace, warn};
-use types::{GenericErrorResponse, GenericNotification, GenericRequest, GenericResponse, ServerCapabilities, Implementation}; // Bring specific types into scope
+use types::{GenericErrorResponse, GenericIncomingResponse, GenericNotification, GenericRequest, GenericResponse, ServerCapabilities, Implementation, ToolAnnotations}; // Bring specific types into scope
 
 // Server state (could be expanded later)
 struct ServerState {
     server_info: Implementation,
     server_capabilities: ServerCapabilities,
-    // Add other stateful data here, e.g., initialized status, client capabilities
-}
-
-/// Runs the main server loop, handling MCP messages over stdio.
-pub async fn run() -> Result<()> {
-    let server_state = ServerState {
-        server_info: Implementation {
-            name: "rust-mcp-stdio-refactored".to_string(),
-            version: "0.1.1".to_string(), // Updated version example
-        },
-        server_capabilities: ServerCapabilities {
-            tools: Some(serde_json::json!({})),     // Indicate capability
-            resources: Some(serde_json::json!({})), // Indicate capability
-            prompts: Some(serde_json::json!({})),   // Indicate capability
-        },
-    };
+    instructions: Option<String>,
+    tools: ToolRegistry,
+    resources: ResourceRegistry,
+    prompts: PromptRegistry,
+    page_size: usize, // Items per page for */list requests
+    peer: Peer, // Outbound messages and per-connection session state
+}
+
+// --- Server Builder ---
+
+/// Configures an MCP server before it starts serving.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use test_rust_mcp_sdk::server::ServerBuilder;
+///
+/// ServerBuilder::new()
+///     .server_info("my-server", "1.0.0")
+///     .instructions("Call `search` before `fetch`.")
+///     .build()
+///     .serve()
+///     .await
+/// # }
+/// ```
+pub struct ServerBuilder {
+    server_info: Implementation,
+    instructions: Option<String>,
+    // None until something is registered; only registries that are set get advertised
+    tools: Option<ToolRegistry>,
+    resources: Option<ResourceRegistry>,
+    prompts: Option<PromptRegistry>,
+    page_size: usize,
+    logging: bool, // Whether a logging layer was handed out
+    transport: Option<Transport>,
+    peer: Peer,
+    outbound_rx: mpsc::UnboundedReceiver<Value>,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel();
+        Self {
+            server_info: Implementation {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            instructions: None,
+            tools: None,
+            resources: None,
+            prompts: None,
+            page_size: DEFAULT_PAGE_SIZE,
+            logging: false,
+            transport: None,
+            peer: Peer::new(outbound_tx, Arc::new(Session::new())),
+            outbound_rx,
+        }
+    }
+}
+
+impl ServerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name and version reported in the `initialize` result.
+    pub fn server_info(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.server_info = Implementation {
+            name: name.into(),
+            version: version.into(),
+        };
+        self
+    }
+
+    /// Sets usage hints for the client, sent back in the `initialize` result.
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Registers a single tool.
+    pub fn tool(mut self, handler: impl ToolHandler + 'static) -> Self {
+        self.tools.get_or_insert_with(ToolRegistry::new).register(handler);
+        self
+    }
+
+    /// Registers a single tool with typed arguments, its input schema derived from them.
+    pub fn typed_tool(mut self, handler: impl TypedToolHandler + 'static) -> Self {
+        self.tools.get_or_insert_with(ToolRegistry::new).register_typed(handler);
+        self
+    }
+
+    /// Registers a single tool with a display title and behaviour hints, e.g. marking it
+    /// destructive so clients ask before running it.
+    pub fn annotated_tool(
+        mut self,
+        handler: impl ToolHandler + 'static,
+        title: Option<String>,
+        annotations: ToolAnnotations,
+    ) -> Self {
+        self.tools
+            .get_or_insert_with(ToolRegistry::new)
+            .register_annotated(handler, title, annotations);
+        self
+    }
+
+    /// Replaces the tool registry wholesale.
+    ///
+    /// The tools capability is advertised even if the registry is empty, so a clone of it
+    /// can be used to add tools once the server is running.
+    pub fn tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Registers a resource provider, which serves both `resources/list` and `resources/read`.
+    pub fn resource_provider(mut self, provider: impl ResourceProvider + 'static) -> Self {
+        self.resources.get_or_insert_with(ResourceRegistry::new).register(provider);
+        self
+    }
+
+    /// Replaces the resource registry wholesale; advertised even if empty, like [`tools`](Self::tools).
+    pub fn resources(mut self, resources: ResourceRegistry) -> Self {
+        self.resources = Some(resources);
+        self
+    }
+
+    /// Registers a prompt, served by `prompts/list` and `prompts/get`.
+    pub fn prompt(mut self, provider: impl PromptProvider + 'static) -> Self {
+        self.prompts.get_or_insert_with(PromptRegistry::new).register(provider);
+        self
+    }
+
+    /// Replaces the prompt registry wholesale; advertised even if empty, like [`tools`](Self::tools).
+    pub fn prompts(mut self, prompts: PromptRegistry) -> Self {
+        self.prompts = Some(prompts);
+        self
+    }
+
+    /// Maximum number of items per `tools/list`, `resources/list`, `resources/templates/list`
+    /// and `prompts/list` response. Defaults to [`DEFAULT_PAGE_SIZE`]; at least 1.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Handle for sending notifications to the client once the server is serving.
+    ///
+    /// Available before `build()` so it can be handed to providers that push updates.
+    pub fn peer(&self) -> Peer {
+        self.peer.clone()
+    }
+
+    /// Creates a `tracing` layer that forwards log events to the client, and advertises
+    /// the `logging` capability. Without it, `logging/setLevel` is not served.
+    pub fn logging_layer(&mut self) -> McpLoggingLayer {
+        self.logging = true;
+        McpLoggingLayer::new(self.peer.clone())
+    }
+
+    /// How long requests sent to the client (sampling, roots, ...) wait for an answer.
+    /// Defaults to [`DEFAULT_REQUEST_TIMEOUT`](crate::peer::DEFAULT_REQUEST_TIMEOUT).
+    pub fn request_timeout(self, timeout: Duration) -> Self {
+        self.peer.set_request_timeout(timeout);
+        self
+    }
 
+    /// Sets the transport. Defaults to stdio.
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    pub fn build(self) -> Server {
+        // Only advertise what we can actually serve. Registries can change at runtime,
+        // which the client hears about through list_changed notifications.
+        let server_capabilities = ServerCapabilities {
+            tools: self.tools.is_some().then(|| serde_json::json!({ "listChanged": true })),
+            resources: self.resources.is_some().then(|| serde_json::json!({ "subscribe": true, "listChanged": true })),
+            prompts: self.prompts.is_some().then(|| serde_json::json!({ "listChanged": true })),
+            logging: self.logging.then(|| serde_json::json!({})), // Only if logs actually reach the client
+        };
+
+        // Only announce changes to registries the client was told about
+        if let Some(tools) = &self.tools {
+            tools.attach(self.peer.clone());
+        }
+        if let Some(resources) = &self.resources {
+            resources.attach(self.peer.clone());
+        }
+        if let Some(prompts) = &self.prompts {
+            prompts.attach(self.peer.clone());
+        }
+
+        Server {
+            state: ServerState {
+                server_info: self.server_info,
+                server_capabilities,
+                instructions: self.instructions,
+                tools: self.tools.unwrap_or_default(),
+                resources: self.resources.unwrap_or_default(),
+                prompts: self.prompts.unwrap_or_default(),
+                page_size: self.page_size,
+                peer: self.peer,
+            },
+            transport: self.transport.unwrap_or_default(),
+            outbound_rx: self.outbound_rx,
+        }
+    }
+}
+
+// --- Server ---
+
+/// A configured server, ready to serve a single client over its transport.
+pub struct Server {
+    state: ServerState,
+    transport: Transport,
+    outbound_rx: mpsc::UnboundedReceiver<Value>,
+}
+
+/// Runs a stdio server exposing the given tools with default settings.
+///
+/// Use [`ServerBuilder`] for anything beyond that.
+pub async fn run(tools: ToolRegistry) -> Result<()> {
+    ServerBuilder::new().tools(tools).build().serve().await
+}
+
+impl Server {
+    /// Handle for sending notifications to the client, e.g. from a background watcher task.
+    pub fn peer(&self) -> Peer {
+        self.state.peer.clone()
+    }
+
+    /// The served tools, or `None` if no tools were configured and the capability is not
+    /// advertised. Tools added or removed through this handle while serving are announced
+    /// to the client with `notifications/tools/list_changed`.
+    pub fn tools(&self) -> Option<ToolRegistry> {
+        let advertised = self.state.server_capabilities.tools.is_some();
+        advertised.then(|| self.state.tools.clone())
+    }
+
+    /// The served resource providers, or `None` if the capability is not advertised;
+    /// see [`tools`](Self::tools).
+    pub fn resources(&self) -> Option<ResourceRegistry> {
+        let advertised = self.state.server_capabilities.resources.is_some();
+        advertised.then(|| self.state.resources.clone())
+    }
+
+    /// The served prompts, or `None` if the capability is not advertised; see [`tools`](Self::tools).
+    pub fn prompts(&self) -> Option<PromptRegistry> {
+        let advertised = self.state.server_capabilities.prompts.is_some();
+        advertised.then(|| self.state.prompts.clone())
+    }
+
+    /// Runs the main server loop until the transport is closed.
+    pub async fn serve(self) -> Result<()> {
+        let Server { state: server_state, transport, outbound_rx } = self;
+        serve_transport(server_state, transport, outbound_rx).await
+    }
+}
+
+/// Owns the transport writer; everything sent to the client goes through here.
+async fn run_writer(mut writer: BoxedWriter, mut outbound_rx: mpsc::UnboundedReceiver<Value>, shutdown: CancellationToken) {
+    loop {
+        tokio::select! {
+            biased; // Drain queued messages before honouring shutdown
+            message = outbound_rx.recv() => {
+                let Some(message) = message else { break };
+                if let Err(e) = stdio::write_message_newline(&mut writer, &message).await {
+                    error!("failed to write outbound message: {:?}", e);
+                    break; // Exit on write error
+                }
+            }
+            _ = shutdown.cancelled() => {
+                while let Ok(message) = outbound_rx.try_recv() {
+                    if let Err(e) = stdio::write_message_newline(&mut writer, &message).await {
+                        error!("failed to write outbound message during shutdown: {:?}", e);
+                        break;
+                    }
+                }
+                break;
+            }
+        }
+    }
+    debug!("writer task finished");
+}
+
+async fn serve_transport(server_state: ServerState, transport: Transport, outbound_rx: mpsc::UnboundedReceiver<Value>) -> Result<()> {
     info!("rust stdio server starting...");
     info!("server info: {:?}", server_state.server_info);
     info!("server capabilities: {:?}", server_state.server_capabilities);
+    info!("registered tools: {}", server_state.tools.len());
+    info!("registered prompts: {}", server_state.prompts.len());
 
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let mut framed_reader = FramedRead::new(BufReader::new(stdin), LinesCodec::new());
+    let Transport { reader, writer } = transport;
+    let mut framed_reader = FramedRead::new(BufReader::new(reader), LinesCodec::new());
+    let shutdown = CancellationToken::new();
+    let writer_task = tokio::spawn(run_writer(writer, outbound_rx, shutdown.clone()));
+    let peer = server_state.peer.clone();
+    let server_state = Arc::new(server_state); // Shared with the per-request tasks
+    let mut in_flight = InFlight::default();
+
+    // Main message loop; request tasks are collected as they finish, so the set doesn't
+    // grow for the whole session and a panicked one is answered straight away
+    loop {
+        let line_result = tokio::select! {
+            line = framed_reader.next() => match line {
+                Some(line) => line,
+                None => break,
+            },
+            Some(result) = in_flight.tasks.join_next_with_id() => {
+                in_flight.finish(result, &peer);
+                continue;
+            }
+        };
+        if peer.is_closed() {
+            error!("writer task has stopped, no longer reading requests");
+            break;
+        }
 
-    // Main message loop
-    while let Some(line_result) = framed_reader.next().await {
         match line_result {
             Ok(line) => {
                 trace!("received raw line: {}", line);
@@ -49,18 +358,41 @@ pub async fn run() -> Result<()> {
                 // Try parsing as a generic structure first to get id/method
                 // Using if-let chain for clarity
                 if let Ok(value) = serde_json::from_str::<Value>(&line) {
-                    if value.get("id").is_some() {
+                    if value.get("method").is_none() && (value.get("result").is_some() || value.get("error").is_some()) {
+                        // A Response to a request we sent the client
+                        match serde_json::from_value::<GenericIncomingResponse>(value) {
+                            Ok(response) => peer.handle_response(response),
+                            Err(e) => error!("failed to parse response from client: {}. line: '{}'", e, line),
+                        }
+                    } else if value.get("id").is_some() {
                         // Likely a Request
                         match serde_json::from_value::<GenericRequest>(value) {
+                            Ok(request) if request.method == "initialize" => {
+                                // Answered before reading on, so a notifications/initialized sent
+                                // right behind it can't overtake the lifecycle change
+                                let ctx = begin_request(&request, &peer);
+                                if let Err(e) = handle_request(&request, &server_state, ctx).await {
+                                    error!("failed to handle request {}: {:?}", request.id, e);
+                                }
+                                peer.session().end_request(&request.id);
+                            }
                             Ok(request) => {
-                                handle_request(&request, &server_state, &mut stdout).await?;
+                                // Each request runs on its own task so a slow tool can't stall the session
+                                let ctx = begin_request(&request, &peer);
+                                let state = Arc::clone(&server_state);
+                                in_flight.spawn(vec![request.id.clone()], async move {
+                                    if let Err(e) = handle_request(&request, &state, ctx).await {
+                                        error!("failed to handle request {}: {:?}", request.id, e);
+                                    }
+                                    state.peer.session().end_request(&request.id);
+                                });
                             }
                             Err(e) => {
                                 error!("failed to parse request: {}. line: '{}'", e, line);
                                 // Try to get ID for error response, even if parsing failed partially
                                 let id = serde_json::from_str::<Value>(&line).ok().and_then(|v| v.get("id").cloned()).unwrap_or(Value::Null);
                                 let err_resp = handlers::parse_error(Some(id), &e.to_string());
-                                if let Err(write_e) = stdio::write_message_newline(&mut stdout, &err_resp).await {
+                                if let Err(write_e) = peer.send(&err_resp) {
                                     error!("failed to write parse error response: {:?}", write_e);
                                     break; // Exit on write error
                                 }
@@ -70,7 +402,7 @@ pub async fn run() -> Result<()> {
                          // Likely a Notification (no ID)
                          match serde_json::from_value::<GenericNotification>(value) {
                              Ok(notification) => {
-                                 handle_notification(&notification, &server_state, &mut stdout).await?;
+                                 handle_notification(&notification, &server_state).await?;
                              }
                              Err(e) => {
                                  // Less critical to respond to notification parse errors, but log it.
@@ -79,6 +411,9 @@ pub async fn run() -> Result<()> {
                                  // For now, just log.
                             }
                          }
+                    } else if let Value::Array(messages) = value {
+                        // A JSON-RPC batch
+                        handle_batch(messages, &server_state, &mut in_flight).await?;
                     } else {
                          // Invalid JSON-RPC message (neither request nor notification)
                          error!("received invalid json-rpc message (no id or method): {}", line);
@@ -88,7 +423,7 @@ pub async fn run() -> Result<()> {
                     // Totally invalid JSON
                     error!("failed to parse incoming line as json: '{}'", line);
                     let err_resp = handlers::parse_error(None, "Invalid JSON received"); // No ID possible
-                    if let Err(write_e) = stdio::write_message_newline(&mut stdout, &err_resp).await {
+                    if let Err(write_e) = peer.send(&err_resp) {
                         error!("failed to write json parse error response: {:?}", write_e);
                         break; // Exit on write error
                     }
@@ -102,55 +437,392 @@ pub async fn run() -> Result<()> {
     }
 
     info!("rust stdio server shutting down.");
+    peer.session().begin_shutdown();
+    // Nothing can answer server-initiated requests any more; fail them so handlers don't hang
+    peer.fail_pending_requests();
+    // The client is gone, so nobody is waiting on in-flight requests: stop them, then let
+    // them wind down so anything already queued is written before the writer stops
+    peer.session().cancel_all_requests();
+    in_flight.join_all(&peer).await;
+    shutdown.cancel();
+    if let Err(e) = writer_task.await {
+        error!("writer task panicked: {:?}", e);
+    }
     Ok(())
 }
 
 
-/// Handles dispatching of incoming requests based on method.
-async fn handle_request(request: &GenericRequest, server_state: &ServerState, stdout: &mut Stdout) -> Result<()> {
+/// Tasks running client requests, with the ids each one must answer.
+#[derive(Default)]
+struct InFlight {
+    tasks: JoinSet<()>,
+    request_ids: HashMap<task::Id, Vec<Value>>, // A batch task answers several requests
+}
+
+impl InFlight {
+    fn spawn(&mut self, request_ids: Vec<Value>, task: impl Future<Output = ()> + Send + 'static) {
+        let handle = self.tasks.spawn(task);
+        self.request_ids.insert(handle.id(), request_ids);
+    }
+
+    async fn join_all(&mut self, peer: &Peer) {
+        while let Some(result) = self.tasks.join_next_with_id().await {
+            self.finish(result, peer);
+        }
+    }
+
+    // A panicked task never answered its requests or stopped tracking them; do both for it
+    fn finish(&mut self, result: Result<(task::Id, ()), JoinError>, peer: &Peer) {
+        let id = match &result {
+            Ok((id, ())) => *id,
+            Err(e) => e.id(),
+        };
+        let request_ids = self.request_ids.remove(&id).unwrap_or_default();
+        let Err(e) = result else {
+            return;
+        };
+        error!("request task for {:?} failed: {:?}", request_ids, e);
+        let reason = if e.is_panic() { "request handler panicked" } else { "request handler was aborted" };
+        for request_id in request_ids {
+            peer.session().end_request(&request_id);
+            let err_resp = handlers::create_error_response(request_id, -32603, format!("Internal error: {}", reason));
+            if let Err(write_e) = peer.send(&err_resp) {
+                error!("failed to write internal error response: {:?}", write_e);
+            }
+        }
+    }
+}
+
+/// Registers a request as in flight and builds the context its handler runs with.
+///
+/// Called before spawning the handler so a cancellation can't race ahead of it.
+fn begin_request(request: &GenericRequest, peer: &Peer) -> RequestContext {
+    let cancellation = peer.session().begin_request(&request.id);
+    let progress_token = types::RequestMeta::from_params(request.params.as_ref()).and_then(|meta| meta.progress_token);
+    RequestContext::new(request.id.clone(), peer.clone(), cancellation, progress_token)
+}
+
+/// Handles an incoming request and sends its response, unless the client cancels it first.
+async fn handle_request(request: &GenericRequest, server_state: &ServerState, ctx: RequestContext) -> Result<()> {
+    let Some(response) = respond_to_request(request, server_state, ctx).await else {
+        return Ok(());
+    };
+    if let Err(e) = server_state.peer.send(&response) {
+        error!("failed to write response for id {}: {:?}", request.id, e);
+        return Err(e); // Propagate write error
+    }
+    info!("sent response for id: {}", request.id);
+    Ok(())
+}
+
+/// Runs a request to completion and returns its response (either success or error),
+/// or `None` if the client cancelled it first.
+async fn respond_to_request(request: &GenericRequest, server_state: &ServerState, ctx: RequestContext) -> Option<Value> {
     info!("received request: id={}, method={}", request.id, request.method);
     debug!("request details: {:?}", request);
 
-    let response_result: Result<Value, GenericErrorResponse> = match request.method.as_str() {
+    let cancellation = ctx.cancellation_token().clone();
+    let response_result = tokio::select! {
+        result = dispatch_request(request, server_state, ctx) => result,
+        _ = cancellation.cancelled() => {
+            // The spec says cancelled requests get no response at all
+            info!("request {} was cancelled, dropping it without a response", request.id);
+            return None;
+        }
+    };
+
+    let response = match response_result {
+        Ok(result_value) => serde_json::to_value(GenericResponse {
+            jsonrpc: "2.0".to_string(),
+            id: request.id.clone(),
+            result: result_value,
+        }),
+        Err(error_response) => serde_json::to_value(error_response),
+    };
+    Some(response.expect("responses serialize to json"))
+}
+
+/// Handles a JSON-RPC batch, if the negotiated protocol version allows batches.
+///
+/// Responses and notifications in the batch are handled right away. Requests run
+/// concurrently on one task, and their responses are sent back as a single array.
+async fn handle_batch(messages: Vec<Value>, server_state: &Arc<ServerState>, in_flight: &mut InFlight) -> Result<()> {
+    let peer = &server_state.peer;
+    let version = peer.session().protocol_version();
+    if !version.is_some_and(|v| v.supports_batching()) {
+        // Also covers batches sent before initialize, which must not be batched
+        warn!("rejecting batch, not supported by protocol version {:?}", version);
+        let err_resp = handlers::invalid_request_error(Value::Null, "batches are not supported by the negotiated protocol version");
+        return peer.send(&err_resp);
+    }
+    if messages.is_empty() {
+        warn!("rejecting empty batch");
+        return peer.send(&handlers::invalid_request_error(Value::Null, "empty batch"));
+    }
+    info!("received batch of {} messages", messages.len());
+
+    let mut requests = Vec::new();
+    let mut errors = Vec::new(); // Responses for malformed entries, sent along with the rest
+    for message in messages {
+        if message.get("method").is_none() && (message.get("result").is_some() || message.get("error").is_some()) {
+            match serde_json::from_value::<GenericIncomingResponse>(message) {
+                Ok(response) => peer.handle_response(response),
+                Err(e) => error!("failed to parse response from client in batch: {}", e),
+            }
+        } else if let Some(id) = message.get("id").cloned() {
+            match serde_json::from_value::<GenericRequest>(message) {
+                Ok(request) => {
+                    let ctx = begin_request(&request, peer);
+                    requests.push((request, ctx));
+                }
+                Err(e) => {
+                    error!("failed to parse request in batch: {}", e);
+                    errors.push(handlers::invalid_request_error(id, &e.to_string()));
+                }
+            }
+        } else if message.get("method").is_some() {
+            match serde_json::from_value::<GenericNotification>(message) {
+                Ok(notification) => handle_notification(&notification, server_state).await?,
+                Err(e) => error!("failed to parse notification in batch: {}", e),
+            }
+        } else {
+            error!("received invalid json-rpc message in batch: {}", message);
+            errors.push(handlers::invalid_request_error(Value::Null, "expected a request, notification or response"));
+        }
+    }
+
+    let state = Arc::clone(server_state);
+    let ids = requests.iter().map(|(request, _)| request.id.clone()).collect();
+    in_flight.spawn(ids, async move {
+        let responses = futures::future::join_all(requests.into_iter().map(|(request, ctx)| {
+            let state = &state;
+            async move {
+                let response = respond_to_request(&request, state, ctx).await;
+                state.peer.session().end_request(&request.id);
+                response
+            }
+        }))
+        .await;
+
+        let mut batch: Vec<Value> = errors
+            .into_iter()
+            .map(|e| serde_json::to_value(e).expect("responses serialize to json"))
+            .collect();
+        batch.extend(responses.into_iter().flatten());
+        // A batch of only notifications, or only cancelled requests, gets no reply at all
+        if batch.is_empty() {
+            return;
+        }
+        match state.peer.send(&batch) {
+            Ok(()) => info!("sent batch response with {} entries", batch.len()),
+            Err(e) => error!("failed to write batch response: {:?}", e),
+        }
+    });
+    Ok(())
+}
+
+/// Handles dispatching of incoming requests based on method.
+async fn dispatch_request(request: &GenericRequest, server_state: &ServerState, ctx: RequestContext) -> Result<Value, GenericErrorResponse> {
+    let session = server_state.peer.session();
+
+    // Only ping is allowed outside the Ready state; initialize does its own check below
+    let state = session.lifecycle();
+    if !matches!(request.method.as_str(), "ping" | "initialize") && state != LifecycleState::Ready {
+        warn!("rejecting {} request while {}", request.method, state);
+        return Err(handlers::lifecycle_error(request.id.clone(), &request.method, state));
+    }
+    // Results are downgraded to what the client's revision can parse
+    let protocol_version = session.protocol_version().unwrap_or(LATEST_PROTOCOL_VERSION);
+
+    match request.method.as_str() {
         "initialize" => {
-            match request.params.clone() { // Clone params for deserialization
+            if let Err(state) = session.begin_initialize() {
+                warn!("rejecting initialize request while {}", state);
+                return Err(handlers::lifecycle_error(request.id.clone(), "initialize", state));
+            }
+            let result = match request.params.clone() { // Clone params for deserialization
                 Some(params_value) => {
                     match serde_json::from_value::<types::InitializeRequestParams>(params_value) {
-                        Ok(params) => handlers::handle_initialize(params, &server_state.server_capabilities, &server_state.server_info)
+                        Ok(params) => handlers::handle_initialize(params, &server_state.server_capabilities, &server_state.server_info, server_state.instructions.clone(), session)
                             .map(|result| serde_json::to_value(result).unwrap()) // Convert result to Value
                             .map_err(|e| handlers::invalid_params_error(request.id.clone(), "initialize", &e.to_string())), // Handler error -> RPC error
                         Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "initialize", &e.to_string())),
                     }
                 }
                 None => Err(handlers::invalid_params_error(request.id.clone(), "initialize", "missing params field")),
+            };
+            if result.is_err() {
+                session.abort_initialize(); // Let the client retry with fixed params
             }
+            result
         }
 
-        "tools/list" => {
-             handlers::handle_list_tools()
+        "ping" => {
+             handlers::handle_ping()
                  .map(|result| serde_json::to_value(result).unwrap())
-                 .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during tools/list: {}", e))) // Generic internal error
+                 .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during ping: {}", e)))
+        }
+
+        "logging/setLevel" if server_state.server_capabilities.logging.is_none() => {
+             warn!("received logging/setLevel, but logging was not enabled");
+             Err(handlers::method_not_found_error(request.id.clone(), &request.method))
+        }
+
+        "logging/setLevel" => {
+             match request.params.clone() {
+                Some(params_value) => {
+                    match serde_json::from_value::<types::SetLevelRequestParams>(params_value) {
+                        Ok(params) => handlers::handle_set_level(server_state.peer.session(), params)
+                            .map(|result| serde_json::to_value(result).unwrap())
+                            .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during logging/setLevel: {}", e))),
+                        Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "logging/setLevel", &e.to_string())),
+                    }
+                }
+                None => Err(handlers::invalid_params_error(request.id.clone(), "logging/setLevel", "missing params field")),
+             }
+        }
+
+        "tools/list" | "tools/call" if server_state.server_capabilities.tools.is_none() => {
+             warn!("received {}, but no tools are served", request.method);
+             Err(handlers::method_not_found_error(request.id.clone(), &request.method))
+        }
+
+        "resources/list" | "resources/templates/list" | "resources/read" | "resources/subscribe" | "resources/unsubscribe"
+            if server_state.server_capabilities.resources.is_none() =>
+        {
+             warn!("received {}, but no resources are served", request.method);
+             Err(handlers::method_not_found_error(request.id.clone(), &request.method))
+        }
+
+        "prompts/list" | "prompts/get" if server_state.server_capabilities.prompts.is_none() => {
+             warn!("received {}, but no prompts are served", request.method);
+             Err(handlers::method_not_found_error(request.id.clone(), &request.method))
+        }
+
+        "tools/list" => {
+             // Params are optional; no cursor means the first page
+             match request.params.clone().map(serde_json::from_value::<types::PaginatedRequestParams>).transpose() {
+                Ok(params) => handlers::handle_list_tools(&server_state.tools, params.unwrap_or_default(), server_state.page_size)
+                    .map(|result| serde_json::to_value(result.for_version(protocol_version)).unwrap())
+                    .map_err(|e| handlers::list_error(request.id.clone(), "tools/list", e)),
+                Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "tools/list", &e.to_string())),
+             }
         }
 
         "resources/list" => {
-             handlers::handle_list_resources()
-                 .map(|result| serde_json::to_value(result).unwrap())
-                 .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during resources/list: {}", e)))
+             // Params are optional; no cursor means the first page
+             match request.params.clone().map(serde_json::from_value::<types::PaginatedRequestParams>).transpose() {
+                Ok(params) => handlers::handle_list_resources(&server_state.resources, params.unwrap_or_default(), server_state.page_size).await
+                    .map(|result| serde_json::to_value(result).unwrap())
+                    .map_err(|e| handlers::list_error(request.id.clone(), "resources/list", e)),
+                Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "resources/list", &e.to_string())),
+             }
+        }
+
+        "resources/templates/list" => {
+             // Params are optional; no cursor means the first page
+             match request.params.clone().map(serde_json::from_value::<types::PaginatedRequestParams>).transpose() {
+                Ok(params) => handlers::handle_list_resource_templates(&server_state.resources, params.unwrap_or_default(), server_state.page_size).await
+                    .map(|result| serde_json::to_value(result).unwrap())
+                    .map_err(|e| handlers::list_error(request.id.clone(), "resources/templates/list", e)),
+                Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "resources/templates/list", &e.to_string())),
+             }
+        }
+
+        "resources/read" => {
+             match request.params.clone() {
+                Some(params_value) => {
+                    match serde_json::from_value::<types::ReadResourceRequestParams>(params_value) {
+                        Ok(params) => {
+                            let uri = params.uri.clone();
+                            match handlers::handle_read_resource(&server_state.resources, params).await {
+                                Ok(Some(result)) => Ok(serde_json::to_value(result).unwrap()),
+                                Ok(None) => {
+                                    warn!("received read for unknown resource: {}", uri);
+                                    Err(handlers::resource_not_found_error(request.id.clone(), &uri))
+                                }
+                                Err(e) => Err(handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during resources/read: {}", e))),
+                            }
+                        }
+                        Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "resources/read", &e.to_string())),
+                    }
+                }
+                None => Err(handlers::invalid_params_error(request.id.clone(), "resources/read", "missing params field")),
+             }
+        }
+
+        "resources/subscribe" | "resources/unsubscribe" => {
+             match request.params.clone() {
+                Some(params_value) => {
+                    match serde_json::from_value::<types::SubscribeRequestParams>(params_value) {
+                        Ok(params) => {
+                            let session = server_state.peer.session();
+                            let result = if request.method == "resources/subscribe" {
+                                handlers::handle_subscribe(session, params)
+                            } else {
+                                handlers::handle_unsubscribe(session, params)
+                            };
+                            result
+                                .map(|result| serde_json::to_value(result).unwrap())
+                                .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during {}: {}", request.method, e)))
+                        }
+                        Err(e) => Err(handlers::invalid_params_error(request.id.clone(), &request.method, &e.to_string())),
+                    }
+                }
+                None => Err(handlers::invalid_params_error(request.id.clone(), &request.method, "missing params field")),
+             }
         }
 
         "prompts/list" => {
-             handlers::handle_list_prompts()
-                 .map(|result| serde_json::to_value(result).unwrap())
-                 .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during prompts/list: {}", e)))
+             // Params are optional; no cursor means the first page
+             match request.params.clone().map(serde_json::from_value::<types::PaginatedRequestParams>).transpose() {
+                Ok(params) => handlers::handle_list_prompts(&server_state.prompts, params.unwrap_or_default(), server_state.page_size)
+                    .map(|result| serde_json::to_value(result).unwrap())
+                    .map_err(|e| handlers::list_error(request.id.clone(), "prompts/list", e)),
+                Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "prompts/list", &e.to_string())),
+             }
+        }
+
+        "prompts/get" => {
+             match request.params.clone() {
+                Some(params_value) => {
+                    match serde_json::from_value::<types::GetPromptRequestParams>(params_value) {
+                        Ok(params) => match server_state.prompts.get(&params.name) {
+                            Some(provider) => {
+                                let missing = prompts::missing_arguments(&provider.prompt(), params.arguments.as_ref().unwrap_or(&Default::default()));
+                                if missing.is_empty() {
+                                    handlers::handle_get_prompt(provider.as_ref(), params).await
+                                        .map(|result| serde_json::to_value(result.for_version(protocol_version)).unwrap())
+                                        .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during prompts/get: {}", e)))
+                                } else {
+                                    Err(handlers::invalid_params_error(request.id.clone(), "prompts/get", &format!("missing required arguments: {}", missing.join(", "))))
+                                }
+                            }
+                            None => {
+                                warn!("received get for unknown prompt: {}", params.name);
+                                Err(handlers::unknown_prompt_error(request.id.clone(), &params.name))
+                            }
+                        },
+                        Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "prompts/get", &e.to_string())),
+                    }
+                }
+                None => Err(handlers::invalid_params_error(request.id.clone(), "prompts/get", "missing params field")),
+             }
         }
 
         "tools/call" => {
              match request.params.clone() {
                 Some(params_value) => {
                     match serde_json::from_value::<types::CallToolRequestParams>(params_value) {
-                        Ok(params) => handlers::handle_call_tool(params)
-                            .map(|result| serde_json::to_value(result).unwrap()) // Convert result to Value
-                            .map_err(|e| handlers::create_error_response(request.id.clone(), -32603, format!("Internal error during tools/call: {}", e))), // Handler error -> RPC error
+                        Ok(params) => match server_state.tools.get(&params.name) {
+                            Some(handler) => handlers::handle_call_tool(handler.as_ref(), params, ctx).await
+                                .map(|result| serde_json::to_value(result.for_version(protocol_version)).unwrap()) // Convert result to Value
+                                .map_err(|e| handlers::tool_call_error(request.id.clone(), e)), // Bad arguments or handler error -> RPC error
+                            None => {
+                                warn!("received call for unknown tool: {}", params.name);
+                                Err(handlers::unknown_tool_error(request.id.clone(), &params.name))
+                            }
+                        },
                         Err(e) => Err(handlers::invalid_params_error(request.id.clone(), "tools/call", &e.to_string())),
                     }
                 }
@@ -162,49 +834,25 @@ async fn handle_request(request: &GenericRequest, server_state: &ServerState, st
             warn!("received unhandled request method: {}", request.method);
             Err(handlers::method_not_found_error(request.id.clone(), &request.method))
         }
-    };
-
-    // Send the response (either success or error)
-    match response_result {
-        Ok(result_value) => {
-            let response = GenericResponse {
-                jsonrpc: "2.0".to_string(),
-                id: request.id.clone(),
-                result: result_value,
-            };
-             if let Err(e) = stdio::write_message_newline(stdout, &response).await {
-                 error!("failed to write success response for id {}: {:?}", request.id, e);
-                 return Err(e.into()); // Propagate write error
-             }
-             info!("sent success response for id: {}", request.id);
-        }
-        Err(error_response) => {
-             if let Err(e) = stdio::write_message_newline(stdout, &error_response).await {
-                 error!("failed to write error response for id {}: {:?}", request.id, e);
-                  return Err(e.into()); // Propagate write error
-             }
-             info!("sent error response for id: {}", request.id);
-        }
     }
-
-    Ok(())
 }
 
 /// Handles dispatching of incoming notifications based on method.
-async fn handle_notification(notification: &GenericNotification, _server_state: &ServerState, _stdout: &mut Stdout) -> Result<()> {
+async fn handle_notification(notification: &GenericNotification, server_state: &ServerState) -> Result<()> {
     info!("received notification: method={}", notification.method);
     debug!("notification details: {:?}", notification);
 
     match notification.method.as_str() {
-         "initialized" => {
+         "notifications/initialized" | "initialized" => { // Bare name kept for older clients
               match notification.params.clone() {
                   Some(params_value) => {
                       match serde_json::from_value::<types::InitializedNotificationParams>(params_value) {
                            Ok(params) => {
-                               if let Err(e) = handlers::handle_initialized(params) {
+                               if let Err(e) = handlers::handle_initialized(params, server_state.peer.session()) {
                                     error!("error handling 'initialized' notification: {:?}", e);
                                     // Decide if an error here is critical enough to stop the server. Usually not for notifications.
                                }
+                               spawn_roots_refresh(&server_state.peer);
                            },
                            Err(e) => {
                                error!("failed to parse 'initialized' params: {}. value: {:?}", e, notification.params);
@@ -213,19 +861,32 @@ async fn handle_notification(notification: &GenericNotification, _server_state:
                       }
                   },
                    None => {
-                       // If params are expected but missing
-                       warn!("'initialized' notification received without expected params (though none currently defined)");
+                       // The usual case: the spec defines no params for this notification
+                       debug!("'initialized' notification received without params");
                        // Handle as if params were empty/default if possible
-                        if let Err(e) = handlers::handle_initialized(Default::default()) {
+                        if let Err(e) = handlers::handle_initialized(Default::default(), server_state.peer.session()) {
                             error!("error handling 'initialized' notification with default params: {:?}", e);
                         }
+                        spawn_roots_refresh(&server_state.peer);
                    }
               }
          }
-         // Add other notification handlers here like $/cancelRequest, etc.
-         "$/cancelRequest" => {
-            warn!("received '$/cancelRequest' notification, but cancellation is not implemented yet.");
-            // TODO: Implement request cancellation logic if needed
+         "notifications/roots/list_changed" => {
+              info!("client roots changed, refreshing");
+              // Until the refresh lands, roots() must ask the client rather than trust the old list
+              server_state.peer.session().invalidate_roots();
+              spawn_roots_refresh(&server_state.peer);
+         }
+         "notifications/cancelled" => {
+              match notification.params.clone().map(serde_json::from_value::<types::CancelledNotificationParams>) {
+                  Some(Ok(params)) => {
+                      if let Err(e) = handlers::handle_cancelled(server_state.peer.session(), params) {
+                          error!("error handling 'notifications/cancelled' notification: {:?}", e);
+                      }
+                  }
+                  Some(Err(e)) => error!("failed to parse 'notifications/cancelled' params: {}. value: {:?}", e, notification.params),
+                  None => error!("'notifications/cancelled' notification received without params"),
+              }
          }
          _ => {
               warn!("received unhandled notification method: {}", notification.method);
@@ -234,3 +895,459 @@ async fn handle_notification(notification: &GenericNotification, _server_state:
     // Notifications typically don't have responses
     Ok(())
 }
+
+/// Fetches the client's roots in the background, if it supports them.
+///
+/// Must not be awaited from the read loop: the response arrives through that same loop.
+fn spawn_roots_refresh(peer: &Peer) {
+    let session = peer.session();
+    if session.lifecycle() != LifecycleState::Ready || !session.client_capabilities().supports_roots() {
+        return;
+    }
+    let peer = peer.clone();
+    tokio::spawn(async move {
+        if let Err(e) = peer.list_roots().await {
+            warn!("failed to fetch roots from client: {:?}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, DuplexStream, Lines, ReadHalf, WriteHalf};
+    use tokio::task::JoinHandle;
+
+    // The client end of a server running over an in-memory duplex
+    struct TestClient {
+        lines: Lines<BufReader<ReadHalf<DuplexStream>>>,
+        writer: WriteHalf<DuplexStream>,
+        server: JoinHandle<Result<()>>,
+    }
+
+    impl TestClient {
+        fn start(builder: ServerBuilder) -> Self {
+            let (client, server) = tokio::io::duplex(1 << 16);
+            let (server_reader, server_writer) = tokio::io::split(server);
+            let server = builder.transport(Transport::new(server_reader, server_writer)).build();
+            let (reader, writer) = tokio::io::split(client);
+            Self {
+                lines: BufReader::new(reader).lines(),
+                writer,
+                server: tokio::spawn(server.serve()),
+            }
+        }
+
+        async fn send(&mut self, message: Value) {
+            let line = format!("{}\n", message);
+            self.writer.write_all(line.as_bytes()).await.unwrap();
+        }
+
+        async fn recv(&mut self) -> Value {
+            let line = tokio::time::timeout(Duration::from_secs(5), self.lines.next_line())
+                .await
+                .expect("timed out waiting for the server")
+                .unwrap()
+                .expect("server closed the connection");
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn request(&mut self, id: u64, method: &str, params: Value) -> Value {
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await;
+            self.recv().await
+        }
+
+        async fn initialize(&mut self, version: &str, capabilities: Value) {
+            let response = self
+                .request(0, "initialize", json!({
+                    "protocolVersion": version,
+                    "capabilities": capabilities,
+                    "clientInfo": { "name": "test", "version": "1" },
+                }))
+                .await;
+            assert_eq!(response["result"]["protocolVersion"], version);
+            self.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+        }
+
+        // Closes the connection and returns whatever the server still sent
+        async fn close(mut self) -> Vec<Value> {
+            self.writer.shutdown().await.unwrap();
+            let mut rest = Vec::new();
+            while let Some(line) = self.lines.next_line().await.unwrap() {
+                rest.push(serde_json::from_str(&line).unwrap());
+            }
+            tokio::time::timeout(Duration::from_secs(5), self.server)
+                .await
+                .expect("server did not shut down")
+                .unwrap()
+                .unwrap();
+            rest
+        }
+    }
+
+    // Answers after a short delay, long enough to be cancelled first
+    struct Slow;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for Slow {
+        fn tool(&self) -> types::Tool {
+            types::Tool {
+                name: "slow".to_string(),
+                ..Default::default()
+            }
+        }
+
+        async fn call(&self, _arguments: Value, _ctx: RequestContext) -> Result<types::CallToolResult> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(types::CallToolResult::default())
+        }
+    }
+
+    // Reports progress if the client asked for it
+    struct Progress;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for Progress {
+        fn tool(&self) -> types::Tool {
+            types::Tool {
+                name: "progress".to_string(),
+                ..Default::default()
+            }
+        }
+
+        async fn call(&self, _arguments: Value, ctx: RequestContext) -> Result<types::CallToolResult> {
+            if let Some(progress) = ctx.progress() {
+                progress.report(1.0, Some(1.0), None)?;
+            }
+            Ok(types::CallToolResult::default())
+        }
+    }
+
+    // Asks the client's LLM and returns its answer
+    struct Ask;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for Ask {
+        fn tool(&self) -> types::Tool {
+            types::Tool {
+                name: "ask".to_string(),
+                ..Default::default()
+            }
+        }
+
+        async fn call(&self, _arguments: Value, ctx: RequestContext) -> Result<types::CallToolResult> {
+            let params = types::CreateMessageRequestParams::new(vec![types::SamplingMessage::user("hi")], 10);
+            let result = ctx.create_message(params).await?;
+            Ok(types::CallToolResult {
+                content: vec![result.content],
+                ..Default::default()
+            })
+        }
+    }
+
+    // Returns the URIs of the client's roots as text
+    struct Roots;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for Roots {
+        fn tool(&self) -> types::Tool {
+            types::Tool {
+                name: "roots".to_string(),
+                ..Default::default()
+            }
+        }
+
+        async fn call(&self, _arguments: Value, ctx: RequestContext) -> Result<types::CallToolResult> {
+            let uris: Vec<_> = ctx.roots().await?.into_iter().map(|root| root.uri).collect();
+            Ok(types::CallToolResult {
+                content: vec![types::ContentPart::text(uris.join(","))],
+                ..Default::default()
+            })
+        }
+    }
+
+    // Answers every roots/list with `uri` until the response to request `id` arrives
+    async fn answer_roots_until(client: &mut TestClient, uri: &str, id: u64) -> Value {
+        loop {
+            let message = client.recv().await;
+            if message["method"] == "roots/list" {
+                let result = json!({ "roots": [{ "uri": uri }] });
+                client.send(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result })).await;
+            } else if message["id"] == id {
+                return message;
+            }
+        }
+    }
+
+    // Takes an integer `n`
+    struct Count;
+
+    #[async_trait::async_trait]
+    impl ToolHandler for Count {
+        fn tool(&self) -> types::Tool {
+            types::Tool {
+                name: "count".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": { "n": { "type": "integer" } },
+                    "required": ["n"],
+                }),
+                ..Default::default()
+            }
+        }
+
+        async fn call(&self, arguments: Value, _ctx: RequestContext) -> Result<types::CallToolResult> {
+            Ok(types::CallToolResult::structured(arguments))
+        }
+    }
+
+    fn initialize_params() -> Value {
+        json!({
+            "protocolVersion": "2025-06-18",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "1" },
+        })
+    }
+
+    #[tokio::test]
+    async fn handshake_messages_sent_back_to_back_are_handled_in_order() {
+        for _ in 0..20 {
+            let mut client = TestClient::start(ServerBuilder::new().tools(ToolRegistry::new()));
+            client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": initialize_params() })).await;
+            client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+            client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).await;
+
+            let response = client.recv().await;
+            assert_eq!(response["id"], 1);
+            assert!(response["result"]["capabilities"]["tools"].is_object());
+            let response = client.recv().await;
+            assert_eq!(response["id"], 2);
+            assert_eq!(response["result"], json!({ "tools": [] }));
+            client.close().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_before_the_handshake_completes_are_rejected() {
+        let mut client = TestClient::start(ServerBuilder::new().tools(ToolRegistry::new()));
+        let response = client.request(1, "tools/list", json!({})).await;
+        assert_eq!(response["error"]["code"], -32600);
+        let response = client.request(2, "ping", json!({})).await;
+        assert_eq!(response["result"], json!({}));
+
+        let response = client.request(3, "initialize", initialize_params()).await;
+        assert!(response["result"].is_object());
+        let response = client.request(4, "tools/list", json!({})).await;
+        assert_eq!(response["error"]["code"], -32600);
+        let response = client.request(5, "initialize", initialize_params()).await;
+        assert_eq!(response["error"]["code"], -32600);
+
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+        let response = client.request(6, "tools/list", json!({})).await;
+        assert_eq!(response["result"], json!({ "tools": [] }));
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn cancelled_requests_get_no_response() {
+        let mut client = TestClient::start(ServerBuilder::new().tool(Slow));
+        client.initialize("2025-06-18", json!({})).await;
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "slow" } })).await;
+        client
+            .send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": 1 } }))
+            .await;
+        client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "slow" } })).await;
+
+        // Request 2 finishes after request 1 would have, so its response comes first only if 1 was dropped
+        let response = client.recv().await;
+        assert_eq!(response["id"], 2);
+        let rest = client.close().await;
+        assert!(rest.iter().all(|message| message["id"] != 1), "unexpected {:?}", rest);
+    }
+
+    #[tokio::test]
+    async fn progress_token_comes_from_meta() {
+        let mut client = TestClient::start(ServerBuilder::new().tool(Progress));
+        client.initialize("2025-06-18", json!({})).await;
+
+        let params = json!({ "name": "progress", "_meta": { "progressToken": "p1" } });
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": params })).await;
+        let notification = client.recv().await;
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(notification["params"]["progressToken"], "p1");
+        assert_eq!(client.recv().await["id"], 1);
+
+        // A malformed _meta carries no token, but doesn't fail the call
+        let response = client.request(2, "tools/call", json!({ "name": "progress", "_meta": 5 })).await;
+        assert!(response["result"].is_object(), "unexpected {}", response);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn set_level_filters_forwarded_events() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mut builder = ServerBuilder::new();
+        let subscriber = tracing_subscriber::registry().with(builder.logging_layer());
+        let _guard = tracing::subscriber::set_default(subscriber); // The test runtime is single-threaded
+        let mut client = TestClient::start(builder);
+        client.initialize("2025-06-18", json!({})).await;
+
+        warn!(marker = "before", "sent before the client picked a level");
+        let response = client.request(1, "logging/setLevel", json!({ "level": "warning" })).await;
+        assert_eq!(response["result"], json!({}));
+        info!(marker = "quiet", "below the level");
+        warn!(marker = "loud", "at the level");
+        client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" })).await;
+
+        let mut forwarded = Vec::new();
+        loop {
+            let message = client.recv().await;
+            if message["id"] == 2 {
+                break;
+            }
+            assert_eq!(message["method"], "notifications/message");
+            forwarded.push(message["params"].clone());
+        }
+        assert!(forwarded.iter().all(|params| params["level"] == "warning" || params["level"] == "error"));
+        let markers: Vec<_> = forwarded.iter().filter_map(|params| params["data"]["marker"].as_str()).collect();
+        assert_eq!(markers, ["loud"]);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn set_level_is_not_served_without_a_logging_layer() {
+        let mut client = TestClient::start(ServerBuilder::new());
+        client.initialize("2025-06-18", json!({})).await;
+        let response = client.request(1, "logging/setLevel", json!({ "level": "info" })).await;
+        assert_eq!(response["error"]["code"], -32601);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn client_responses_reach_the_waiting_request() {
+        let mut client = TestClient::start(ServerBuilder::new().tool(Ask));
+        client.initialize("2025-06-18", json!({ "sampling": {} })).await;
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "ask" } })).await;
+
+        let request = client.recv().await;
+        assert_eq!(request["method"], "sampling/createMessage");
+        let result = json!({ "role": "assistant", "content": { "type": "text", "text": "hello" }, "model": "m" });
+        client.send(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })).await;
+
+        let response = client.recv().await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["content"][0]["text"], "hello");
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn unanswered_client_requests_time_out_and_are_cancelled() {
+        let builder = ServerBuilder::new().tool(Ask).request_timeout(Duration::from_millis(50));
+        let mut client = TestClient::start(builder);
+        client.initialize("2025-06-18", json!({ "sampling": {} })).await;
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "ask" } })).await;
+
+        let request = client.recv().await;
+        assert_eq!(request["method"], "sampling/createMessage");
+        let cancelled = client.recv().await;
+        assert_eq!(cancelled["method"], "notifications/cancelled");
+        assert_eq!(cancelled["params"]["requestId"], request["id"]);
+        let response = client.recv().await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], -32603);
+
+        // A late answer is dropped without disturbing the session
+        let result = json!({ "role": "assistant", "content": { "type": "text", "text": "late" }, "model": "m" });
+        client.send(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })).await;
+        assert_eq!(client.request(2, "ping", json!({})).await["id"], 2);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn changed_roots_are_never_served_from_the_old_cache() {
+        let mut client = TestClient::start(ServerBuilder::new().tool(Roots));
+        client.initialize("2025-06-18", json!({ "roots": { "listChanged": true } })).await;
+        let call = json!({ "name": "roots" });
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": call })).await;
+        let response = answer_roots_until(&mut client, "file:///old", 1).await;
+        assert_eq!(response["result"]["content"][0]["text"], "file:///old");
+
+        // The tool runs before the background refresh is answered, so it must not see the old roots
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/roots/list_changed" })).await;
+        client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": call })).await;
+        let response = answer_roots_until(&mut client, "file:///new", 2).await;
+        assert_eq!(response["result"]["content"][0]["text"], "file:///new");
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn methods_of_unadvertised_capabilities_are_not_found() {
+        let mut client = TestClient::start(ServerBuilder::new().tools(ToolRegistry::new()));
+        client.initialize("2025-06-18", json!({})).await;
+        assert_eq!(client.request(1, "tools/list", json!({})).await["result"], json!({ "tools": [] }));
+        for (id, method) in [(2, "resources/list"), (3, "resources/templates/list"), (4, "prompts/list")] {
+            let response = client.request(id, method, json!({})).await;
+            assert_eq!(response["error"]["code"], -32601, "{}", method);
+        }
+        let response = client.request(5, "resources/read", json!({ "uri": "file:///a" })).await;
+        assert_eq!(response["error"]["code"], -32601);
+        let response = client.request(6, "prompts/get", json!({ "name": "p" })).await;
+        assert_eq!(response["error"]["code"], -32601);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn batches_get_one_array_reply_on_2025_03_26() {
+        let mut client = TestClient::start(ServerBuilder::new().tools(ToolRegistry::new()));
+        client.initialize("2025-03-26", json!({})).await;
+        client
+            .send(json!([
+                { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+                { "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": 99 } },
+                { "jsonrpc": "2.0", "id": 2, "method": "tools/list" },
+            ]))
+            .await;
+
+        let reply = client.recv().await;
+        let mut ids: Vec<_> = reply.as_array().expect("an array reply").iter().map(|r| r["id"].clone()).collect();
+        ids.sort_by_key(|id| id.as_u64());
+        assert_eq!(ids, [json!(1), json!(2)]);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn batches_are_rejected_on_other_versions() {
+        for version in ["2024-11-05", "2025-06-18"] {
+            let mut client = TestClient::start(ServerBuilder::new());
+            client.initialize(version, json!({})).await;
+            client.send(json!([{ "jsonrpc": "2.0", "id": 1, "method": "ping" }])).await;
+
+            let reply = client.recv().await;
+            assert_eq!(reply["id"], Value::Null, "{}", version);
+            assert_eq!(reply["error"]["code"], -32600, "{}", version);
+            let rest = client.close().await;
+            assert!(rest.iter().all(|message| message["id"] != 1), "{}: {:?}", version, rest);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_tool_arguments_are_rejected_with_violations() {
+        let mut client = TestClient::start(ServerBuilder::new().tool(Count));
+        client.initialize("2025-06-18", json!({})).await;
+
+        let response = client.request(1, "tools/call", json!({ "name": "count", "arguments": { "n": "x" } })).await;
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(response["error"]["data"]["violations"][0]["path"], "/n");
+
+        let response = client.request(2, "tools/call", json!({ "name": "count" })).await;
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(response["error"]["data"]["violations"][0]["path"], "");
+
+        let response = client.request(3, "tools/call", json!({ "name": "count", "arguments": { "n": 2 } })).await;
+        assert_eq!(response["result"]["structuredContent"], json!({ "n": 2 }));
+        client.close().await;
+    }
+}
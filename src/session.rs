@@ -0,0 +1,235 @@
+use crate::types::{ClientCapabilities, Implementation, LoggingLevel, ProtocolVersion, Root};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Where the connection is in the MCP lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LifecycleState {
+    /// Nothing but `initialize` (and `ping`) is accepted yet.
+    #[default]
+    AwaitingInitialize,
+    /// `initialize` was handled; waiting for the client's `notifications/initialized`.
+    Initializing,
+    /// Handshake complete, all requests are served.
+    Ready,
+    /// The transport closed; in-flight requests are finishing.
+    ShuttingDown,
+}
+
+impl std::fmt::Display for LifecycleState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::AwaitingInitialize => "awaiting initialize",
+            Self::Initializing => "initializing",
+            Self::Ready => "ready",
+            Self::ShuttingDown => "shutting down",
+        })
+    }
+}
+
+/// Per-connection state shared between the message loop and [`Peer`](crate::peer::Peer) handles.
+#[derive(Debug, Default)]
+pub struct Session {
+    lifecycle: Mutex<LifecycleState>,
+    subscriptions: Mutex<HashSet<String>>, // Resource URIs the client subscribed to
+    in_flight: Mutex<HashMap<String, CancellationToken>>, // Keyed by serialized request id
+    log_level: Mutex<Option<LoggingLevel>>, // Set by logging/setLevel
+    protocol_version: Mutex<Option<ProtocolVersion>>, // Negotiated during initialize
+    client_capabilities: Mutex<Option<ClientCapabilities>>, // Sent with initialize
+    client_info: Mutex<Option<Implementation>>,
+    roots: Mutex<RootsCache>,
+}
+
+// Last answer to roots/list, and how many times the client said its roots changed
+#[derive(Debug, Default)]
+struct RootsCache {
+    roots: Option<Vec<Root>>,
+    generation: u64,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a subscription. Returns `false` if the client was already subscribed.
+    pub fn subscribe(&self, uri: &str) -> bool {
+        self.subscriptions.lock().unwrap().insert(uri.to_string())
+    }
+
+    /// Drops a subscription. Returns `false` if the client was not subscribed.
+    pub fn unsubscribe(&self, uri: &str) -> bool {
+        self.subscriptions.lock().unwrap().remove(uri)
+    }
+
+    pub fn is_subscribed(&self, uri: &str) -> bool {
+        self.subscriptions.lock().unwrap().contains(uri)
+    }
+
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.subscriptions.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn lifecycle(&self) -> LifecycleState {
+        *self.lifecycle.lock().unwrap()
+    }
+
+    /// Moves from `AwaitingInitialize` to `Initializing`. Fails with the current state
+    /// otherwise, e.g. on a second `initialize`.
+    pub(crate) fn begin_initialize(&self) -> Result<(), LifecycleState> {
+        let mut lifecycle = self.lifecycle.lock().unwrap();
+        match *lifecycle {
+            LifecycleState::AwaitingInitialize => {
+                *lifecycle = LifecycleState::Initializing;
+                Ok(())
+            }
+            state => Err(state),
+        }
+    }
+
+    /// Undoes `begin_initialize` when the `initialize` request itself was rejected.
+    pub(crate) fn abort_initialize(&self) {
+        let mut lifecycle = self.lifecycle.lock().unwrap();
+        if *lifecycle == LifecycleState::Initializing {
+            *lifecycle = LifecycleState::AwaitingInitialize;
+        }
+    }
+
+    /// Moves from `Initializing` to `Ready`. Fails with the current state otherwise.
+    pub(crate) fn mark_ready(&self) -> Result<(), LifecycleState> {
+        let mut lifecycle = self.lifecycle.lock().unwrap();
+        match *lifecycle {
+            LifecycleState::Initializing => {
+                *lifecycle = LifecycleState::Ready;
+                Ok(())
+            }
+            state => Err(state),
+        }
+    }
+
+    pub(crate) fn begin_shutdown(&self) {
+        *self.lifecycle.lock().unwrap() = LifecycleState::ShuttingDown;
+    }
+
+    /// The protocol version agreed on during `initialize`, or `None` before that.
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        *self.protocol_version.lock().unwrap()
+    }
+
+    pub(crate) fn set_protocol_version(&self, version: ProtocolVersion) {
+        *self.protocol_version.lock().unwrap() = Some(version);
+    }
+
+    /// Capabilities the client declared in `initialize`; empty before that.
+    pub fn client_capabilities(&self) -> ClientCapabilities {
+        self.client_capabilities.lock().unwrap().clone().unwrap_or_default()
+    }
+
+    /// Name and version the client declared in `initialize`.
+    pub fn client_info(&self) -> Option<Implementation> {
+        self.client_info.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_client(&self, info: Implementation, capabilities: ClientCapabilities) {
+        *self.client_info.lock().unwrap() = Some(info);
+        *self.client_capabilities.lock().unwrap() = Some(capabilities);
+    }
+
+    /// The client's roots as of the last `roots/list`, or `None` if never fetched or
+    /// changed since.
+    pub fn roots(&self) -> Option<Vec<Root>> {
+        self.roots.lock().unwrap().roots.clone()
+    }
+
+    /// Forgets the cached roots after the client said they changed.
+    pub(crate) fn invalidate_roots(&self) {
+        let mut cache = self.roots.lock().unwrap();
+        cache.roots = None;
+        cache.generation += 1;
+    }
+
+    /// Identifies the current roots; taken before asking the client for them.
+    pub(crate) fn roots_generation(&self) -> u64 {
+        self.roots.lock().unwrap().generation
+    }
+
+    /// Caches `roots` fetched at `generation`. Returns `false`, caching nothing, if the
+    /// roots changed while they were being fetched.
+    pub(crate) fn set_roots(&self, roots: Vec<Root>, generation: u64) -> bool {
+        let mut cache = self.roots.lock().unwrap();
+        if cache.generation != generation {
+            return false;
+        }
+        cache.roots = Some(roots);
+        true
+    }
+
+    /// Minimum level for `notifications/message`, if the client has chosen one.
+    pub fn log_level(&self) -> Option<LoggingLevel> {
+        *self.log_level.lock().unwrap()
+    }
+
+    pub fn set_log_level(&self, level: LoggingLevel) {
+        *self.log_level.lock().unwrap() = Some(level);
+    }
+
+    /// Tracks a request that is about to be handled and returns its cancellation token.
+    pub(crate) fn begin_request(&self, id: &Value) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.in_flight.lock().unwrap().insert(id.to_string(), token.clone());
+        token
+    }
+
+    /// Stops tracking a request once its handler has finished (or was cancelled).
+    pub(crate) fn end_request(&self, id: &Value) {
+        self.in_flight.lock().unwrap().remove(&id.to_string());
+    }
+
+    /// Cancels every in-flight request, e.g. once the client has gone away.
+    pub(crate) fn cancel_all_requests(&self) {
+        for (_, token) in self.in_flight.lock().unwrap().drain() {
+            token.cancel();
+        }
+    }
+
+    /// Cancels an in-flight request. Returns `false` if no such request is running,
+    /// e.g. because it already completed.
+    pub fn cancel_request(&self, id: &Value) -> bool {
+        match self.in_flight.lock().unwrap().remove(&id.to_string()) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots(uri: &str) -> Vec<Root> {
+        vec![Root {
+            uri: uri.to_string(),
+            name: None,
+        }]
+    }
+
+    #[test]
+    fn roots_fetched_before_a_change_are_not_cached() {
+        let session = Session::new();
+        let stale = session.roots_generation();
+        session.invalidate_roots();
+        let fresh = session.roots_generation();
+
+        assert!(session.set_roots(roots("file:///new"), fresh));
+        assert!(!session.set_roots(roots("file:///old"), stale));
+        assert_eq!(session.roots(), Some(roots("file:///new")));
+
+        session.invalidate_roots();
+        assert_eq!(session.roots(), None);
+    }
+}
@@ -1,10 +1,13 @@
 use anyhow::Result;
 use serde::Serialize;
-use tokio::io::{AsyncWriteExt, Stdout};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tracing::debug;
 
-/// Writes a JSON message to the writer (stdout), followed by a newline.
-pub async fn write_message_newline(writer: &mut Stdout, message: &impl Serialize) -> Result<()> {
+/// Writes a JSON message to the writer (usually stdout), followed by a newline.
+pub async fn write_message_newline<W>(writer: &mut W, message: &impl Serialize) -> Result<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
     let message_str = serde_json::to_string(message)?;
     debug!("sending raw json: {}", message_str); // Log the JSON being sent
 
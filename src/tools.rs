@@ -0,0 +1,261 @@
+use crate::context::RequestContext;
+use crate::registry::{Registry, RegistryItem};
+use crate::schema::{self, SchemaViolation, Validator};
+use crate::types::{CallToolResult, Tool, ToolAnnotations};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::warn;
+
+// --- Tool Handler Trait ---
+
+/// Implemented by anything that can be exposed to clients as an MCP tool.
+#[async_trait]
+pub trait ToolHandler: Send + Sync {
+    /// The tool definition advertised in `tools/list`. Read once, when the tool is registered.
+    fn tool(&self) -> Tool;
+
+    /// Executes the tool with the arguments sent by the client.
+    ///
+    /// Errors returned here are reported as JSON-RPC internal errors. Failures the
+    /// model should see (bad input, upstream errors) belong in a `CallToolResult`
+    /// with `is_error: Some(true)` instead.
+    async fn call(&self, arguments: Value, ctx: RequestContext) -> Result<CallToolResult>;
+}
+
+// --- Typed Tool Handler Trait ---
+
+/// A tool whose arguments are deserialized into `Args` before it runs.
+///
+/// The advertised `input_schema` is derived from `Args` with `schemars`, so it can't
+/// drift from what the handler accepts. Register with [`ToolRegistry::register_typed`].
+///
+/// Deriving `JsonSchema` needs `schemars` as a dependency, or, as below, the crate
+/// attribute pointing at [the re-export](crate::schemars).
+///
+/// ```no_run
+/// use anyhow::{anyhow, Result};
+/// use async_trait::async_trait;
+/// use serde::Deserialize;
+/// use test_rust_mcp_sdk::schemars::JsonSchema;
+/// use test_rust_mcp_sdk::tools::TypedToolHandler;
+/// use test_rust_mcp_sdk::types::{CallToolResult, ContentPart, Tool};
+/// use test_rust_mcp_sdk::RequestContext;
+///
+/// #[derive(Deserialize, JsonSchema)]
+/// #[schemars(crate = "test_rust_mcp_sdk::schemars")]
+/// struct SearchArgs {
+///     /// What to look for.
+///     query: String,
+///     limit: Option<u32>,
+/// }
+///
+/// struct Search;
+///
+/// #[async_trait]
+/// impl TypedToolHandler for Search {
+///     type Args = SearchArgs;
+///
+///     fn tool(&self) -> Tool {
+///         Tool {
+///             name: "search".to_string(),
+///             description: Some("Searches the index.".to_string()),
+///             ..Default::default()
+///         }
+///     }
+///
+///     async fn call(&self, args: SearchArgs, _ctx: RequestContext) -> Result<CallToolResult> {
+///         Ok(CallToolResult {
+///             content: vec![ContentPart::text(format!("results for {}", args.query))],
+///             ..Default::default()
+///         })
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait TypedToolHandler: Send + Sync {
+    type Args: DeserializeOwned + JsonSchema + Send;
+
+    /// The tool definition advertised in `tools/list`. Its `input_schema` is ignored and
+    /// replaced with the schema of [`Args`](Self::Args).
+    fn tool(&self) -> Tool;
+
+    /// Executes the tool. Arguments that don't deserialize into `Args` are rejected as
+    /// invalid params before this is called.
+    async fn call(&self, args: Self::Args, ctx: RequestContext) -> Result<CallToolResult>;
+}
+
+// Adapts a typed handler to the untyped trait the registry stores
+struct Typed<T> {
+    handler: T,
+    input_schema: Value, // Derived from T::Args once, at registration
+}
+
+impl<T: TypedToolHandler> Typed<T> {
+    fn new(handler: T) -> Self {
+        let input_schema = serde_json::to_value(schemars::schema_for!(T::Args)).expect("schemas serialize to json");
+        Self { handler, input_schema }
+    }
+}
+
+#[async_trait]
+impl<T: TypedToolHandler> ToolHandler for Typed<T> {
+    fn tool(&self) -> Tool {
+        Tool {
+            input_schema: self.input_schema.clone(),
+            ..self.handler.tool()
+        }
+    }
+
+    async fn call(&self, arguments: Value, ctx: RequestContext) -> Result<CallToolResult> {
+        let args = serde_json::from_value(arguments).map_err(|e| InvalidArguments {
+            tool: self.handler.tool().name,
+            violations: vec![SchemaViolation {
+                path: String::new(), // serde_json doesn't say where it failed, only the line/column
+                message: e.to_string(),
+            }],
+        })?;
+        self.handler.call(args, ctx).await
+    }
+}
+
+/// Returned (wrapped in `anyhow::Error`) when a tool's arguments are rejected before it runs.
+///
+/// Reported to the client as invalid params (-32602), with every violation in the error's `data`.
+#[derive(Debug, Clone)]
+pub struct InvalidArguments {
+    pub tool: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl std::fmt::Display for InvalidArguments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid arguments for tool '{}': {}", self.tool, schema::describe(&self.violations))
+    }
+}
+
+impl std::error::Error for InvalidArguments {}
+
+// Overrides the title and annotations of a wrapped handler's definition
+struct Annotated<H> {
+    handler: H,
+    title: Option<String>,
+    annotations: ToolAnnotations,
+}
+
+#[async_trait]
+impl<H: ToolHandler> ToolHandler for Annotated<H> {
+    fn tool(&self) -> Tool {
+        let tool = self.handler.tool();
+        Tool {
+            title: self.title.clone().or(tool.title),
+            annotations: Some(self.annotations.clone()),
+            ..tool
+        }
+    }
+
+    async fn call(&self, arguments: Value, ctx: RequestContext) -> Result<CallToolResult> {
+        self.handler.call(arguments, ctx).await
+    }
+}
+
+// --- Tool Registry ---
+
+/// A registered tool: its handler, with the definition and schema validators taken
+/// from it at registration.
+pub struct RegisteredTool {
+    tool: Tool,
+    handler: Box<dyn ToolHandler>,
+    // Compile errors are kept so that calls fail, as with any other handler error
+    input_validator: Result<Validator, String>,
+    output_validator: Option<Result<Validator, String>>,
+}
+
+impl RegisteredTool {
+    fn new(handler: impl ToolHandler + 'static) -> Self {
+        let tool = handler.tool();
+        let compile = |schema: &Value| {
+            Validator::new(schema).map_err(|e| {
+                warn!("tool '{}' has an unusable schema: {}", tool.name, e);
+                e.to_string()
+            })
+        };
+        let input_validator = compile(&tool.input_schema);
+        let output_validator = tool.output_schema.as_ref().map(compile);
+        Self {
+            handler: Box::new(handler),
+            input_validator,
+            output_validator,
+            tool,
+        }
+    }
+
+    pub fn tool(&self) -> &Tool {
+        &self.tool
+    }
+
+    pub fn handler(&self) -> &dyn ToolHandler {
+        self.handler.as_ref()
+    }
+
+    /// Violations of the input schema in `arguments`. Fails if the schema didn't compile.
+    pub fn validate_arguments(&self, arguments: &Value) -> Result<Vec<SchemaViolation>> {
+        match &self.input_validator {
+            Ok(validator) => Ok(validator.validate(arguments)),
+            Err(e) => Err(anyhow!("{}", e)),
+        }
+    }
+
+    /// Violations of the output schema in `structured_content`; none if the tool has no
+    /// output schema. Fails if the schema didn't compile.
+    pub fn validate_output(&self, structured_content: &Value) -> Result<Vec<SchemaViolation>> {
+        match &self.output_validator {
+            None => Ok(Vec::new()),
+            Some(Ok(validator)) => Ok(validator.validate(structured_content)),
+            Some(Err(e)) => Err(anyhow!("{}", e)),
+        }
+    }
+}
+
+/// Holds the tools served by this server, keyed by name; see [`Registry`].
+pub type ToolRegistry = Registry<RegisteredTool>;
+
+impl RegistryItem for RegisteredTool {
+    const LIST_CHANGED: &'static str = "notifications/tools/list_changed";
+}
+
+impl Registry<RegisteredTool> {
+    /// Registers a tool. A tool registered under an existing name replaces the old one.
+    pub fn register(&self, handler: impl ToolHandler + 'static) {
+        let registered = RegisteredTool::new(handler);
+        self.insert(registered.tool.name.clone(), Arc::new(registered));
+    }
+
+    /// Registers a tool with typed arguments; see [`TypedToolHandler`].
+    pub fn register_typed(&self, handler: impl TypedToolHandler + 'static) {
+        self.register(Typed::new(handler));
+    }
+
+    /// Registers a tool with annotations and optionally a display title, replacing those
+    /// the handler's own definition declares.
+    pub fn register_annotated(
+        &self,
+        handler: impl ToolHandler + 'static,
+        title: Option<String>,
+        annotations: ToolAnnotations,
+    ) {
+        self.register(Annotated {
+            handler,
+            title,
+            annotations,
+        });
+    }
+
+    /// Tool definitions in registration order.
+    pub fn list(&self) -> Vec<Tool> {
+        self.items().iter().map(|registered| registered.tool.clone()).collect()
+    }
+}
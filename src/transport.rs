@@ -0,0 +1,36 @@
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+pub type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+pub type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// The byte streams a server reads requests from and writes responses to.
+///
+/// Messages are newline-delimited JSON in both directions.
+pub struct Transport {
+    pub(crate) reader: BoxedReader,
+    pub(crate) writer: BoxedWriter,
+}
+
+impl Transport {
+    /// Uses the process stdin/stdout, which is what MCP hosts expect when launching a server.
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+
+    /// Uses an arbitrary reader/writer pair, e.g. a socket or an in-memory duplex for embedding.
+    pub fn new(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::stdio()
+    }
+}
@@ -1,8 +1,76 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 
 // --- MCP Type Definitions ---
 
+/// MCP protocol revisions known to this crate, ordered oldest to newest.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    #[serde(rename = "2024-11-05")]
+    V2024_11_05,
+    #[serde(rename = "2025-03-26")]
+    V2025_03_26,
+    #[serde(rename = "2025-06-18")]
+    V2025_06_18,
+}
+
+impl ProtocolVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::V2024_11_05 => "2024-11-05",
+            Self::V2025_03_26 => "2025-03-26",
+            Self::V2025_06_18 => "2025-06-18",
+        }
+    }
+
+    /// Parses a revision string, returning `None` for revisions this crate doesn't know.
+    pub fn parse(version: &str) -> Option<Self> {
+        match version {
+            "2024-11-05" => Some(Self::V2024_11_05),
+            "2025-03-26" => Some(Self::V2025_03_26),
+            "2025-06-18" => Some(Self::V2025_06_18),
+            _ => None,
+        }
+    }
+
+    /// Whether JSON-RPC batches are allowed: added in 2025-03-26, removed again in 2025-06-18.
+    pub fn supports_batching(self) -> bool {
+        self == Self::V2025_03_26
+    }
+
+    /// Whether audio content exists: added in 2025-03-26.
+    pub fn supports_audio(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Whether tools carry behaviour annotations: added in 2025-03-26.
+    pub fn supports_tool_annotations(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Whether tools can declare an output schema and return structured content: added in 2025-06-18.
+    pub fn supports_structured_content(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// Whether tool results and prompt messages can contain `resource_link` content: added in 2025-06-18.
+    pub fn supports_resource_links(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// Whether definitions carry a display `title` next to their name: added in 2025-06-18.
+    pub fn supports_titles(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Implementation {
@@ -13,16 +81,62 @@ pub struct Implementation {
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ServerCapabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub resources: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prompts: Option<Value>,
-    // Add other capabilities like logging, sampling as needed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Value>,
+    // Add other capabilities like completions as needed
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ClientCapabilities {
-    // Define client capabilities if needed for logic later
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roots: Option<RootsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Value>, // Presence is what matters; no fields defined yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elicitation: Option<Value>, // Likewise, added in 2025-06-18
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experimental: Option<HashMap<String, Value>>,
+}
+
+impl ClientCapabilities {
+    /// Whether the client can answer `roots/list`.
+    pub fn supports_roots(&self) -> bool {
+        self.roots.is_some()
+    }
+
+    /// Whether the client sends `notifications/roots/list_changed`.
+    pub fn supports_roots_list_changed(&self) -> bool {
+        self.roots.as_ref().and_then(|r| r.list_changed).unwrap_or(false)
+    }
+
+    /// Whether the client can answer `sampling/createMessage`.
+    pub fn supports_sampling(&self) -> bool {
+        self.sampling.is_some()
+    }
+
+    /// Whether the client can answer `elicitation/create`.
+    pub fn supports_elicitation(&self) -> bool {
+        self.elicitation.is_some()
+    }
+
+    /// Whether the client advertised the named experimental capability.
+    pub fn supports_experimental(&self, name: &str) -> bool {
+        self.experimental.as_ref().is_some_and(|e| e.contains_key(name))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RootsCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_changed: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -59,10 +173,40 @@ pub struct GenericResponse<T> {
     pub result: T,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ErrorData {
     pub code: i32,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl std::fmt::Display for ErrorData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "json-rpc error {}: {}", self.code, self.message)
+    }
+}
+
+// Lets callers of Peer::request downcast the client's error from anyhow
+impl std::error::Error for ErrorData {}
+
+// A response from the client to a request the server sent
+#[derive(Deserialize, Debug)]
+pub struct GenericIncomingResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub result: Option<Value>,
+    pub error: Option<ErrorData>,
+}
+
+// Outgoing request from the server to the client
+#[derive(Serialize, Debug)]
+pub struct GenericOutgoingRequest {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
 }
 
 #[derive(Serialize, Debug)]
@@ -79,8 +223,77 @@ pub struct GenericErrorResponse {
 pub struct Tool {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>, // Display name; clients fall back to name
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     pub input_schema: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>, // Schema structuredContent is checked against before sending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+impl Default for Tool {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            title: None,
+            description: None,
+            input_schema: serde_json::json!({ "type": "object" }), // Takes no arguments
+            output_schema: None,
+            annotations: None,
+        }
+    }
+}
+
+/// Hints about a tool's behaviour, e.g. for clients deciding whether to ask for confirmation.
+///
+/// These are hints only: clients must not trust them from untrusted servers. Unset hints
+/// take the spec's defaults, which the accessors below resolve.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>, // Doesn't modify its environment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>, // May delete or overwrite; only meaningful if not read-only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>, // Repeating a call with the same arguments has no further effect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>, // Talks to external systems, e.g. the web
+}
+
+impl Tool {
+    /// Drops the fields a client speaking `version` doesn't know about.
+    pub fn for_version(self, version: ProtocolVersion) -> Self {
+        Self {
+            title: self.title.filter(|_| version.supports_titles()),
+            output_schema: self.output_schema.filter(|_| version.supports_structured_content()),
+            annotations: self.annotations.filter(|_| version.supports_tool_annotations()),
+            ..self
+        }
+    }
+}
+
+impl ToolAnnotations {
+    pub fn is_read_only(&self) -> bool {
+        self.read_only_hint.unwrap_or(false)
+    }
+
+    /// Read-only tools are never destructive; others are unless they say otherwise.
+    pub fn is_destructive(&self) -> bool {
+        !self.is_read_only() && self.destructive_hint.unwrap_or(true)
+    }
+
+    pub fn is_idempotent(&self) -> bool {
+        self.is_read_only() || self.idempotent_hint.unwrap_or(false)
+    }
+
+    pub fn is_open_world(&self) -> bool {
+        self.open_world_hint.unwrap_or(true)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -90,6 +303,20 @@ pub struct Resource {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A parameterised family of resources, e.g. `db://tables/{table}/rows/{id}` (RFC 6570).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -114,22 +341,171 @@ pub struct Prompt {
 
 // --- MCP Response Types for Lists ---
 
+/// Params of every `*/list` request.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedRequestParams {
+    #[serde(default)]
+    pub cursor: Option<String>, // A nextCursor from an earlier page
+}
+
 #[derive(Serialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ListToolsResult {
     pub tools: Vec<Tool>, // Use the specific Tool struct
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>, // Set when there are more pages
+}
+
+impl ListToolsResult {
+    /// Downgrades every tool for a client speaking `version`; see [`Tool::for_version`].
+    pub fn for_version(self, version: ProtocolVersion) -> Self {
+        Self {
+            tools: self.tools.into_iter().map(|tool| tool.for_version(version)).collect(),
+            ..self
+        }
+    }
 }
 
 #[derive(Serialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ListResourcesResult {
     pub resources: Vec<Resource>, // Use the specific Resource struct
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>, // Set when there are more pages
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResourceTemplatesResult {
+    pub resource_templates: Vec<ResourceTemplate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>, // Set when there are more pages
 }
 
 #[derive(Serialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ListPromptsResult {
     pub prompts: Vec<Prompt>, // Use the specific Prompt struct
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>, // Set when there are more pages
+}
+
+// --- Resource Read Specific Structs ---
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadResourceRequestParams {
+    pub uri: String, // URI of the resource to read
+}
+
+/// Contents of a resource: exactly one of `text` or `blob` (base64) is set.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+impl ResourceContents {
+    pub fn text(uri: impl Into<String>, mime_type: Option<String>, text: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            mime_type,
+            text: Some(text.into()),
+            blob: None,
+        }
+    }
+
+    /// Binary contents; `data` is base64-encoded for the wire.
+    pub fn blob(uri: impl Into<String>, mime_type: Option<String>, data: &[u8]) -> Self {
+        use base64::Engine;
+        Self {
+            uri: uri.into(),
+            mime_type,
+            text: None,
+            blob: Some(base64::engine::general_purpose::STANDARD.encode(data)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadResourceResult {
+    pub contents: Vec<ResourceContents>,
+}
+
+// --- Resource Subscription Structs ---
+
+// Shared by resources/subscribe and resources/unsubscribe
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscribeRequestParams {
+    pub uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUpdatedNotificationParams {
+    pub uri: String,
+}
+
+// Result for methods that only acknowledge, serialized as `{}`
+#[derive(Serialize, Debug, Default)]
+pub struct EmptyResult {}
+
+// --- Logging Specific Structs ---
+
+/// Syslog severities used by MCP, ordered from least to most severe.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LoggingLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLevelRequestParams {
+    pub level: LoggingLevel,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggingMessageNotificationParams {
+    pub level: LoggingLevel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    pub data: Value, // Any JSON; we send the message and structured fields
+}
+
+// --- Request Metadata ---
+
+// The `_meta` object any request's params may carry
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMeta {
+    pub progress_token: Option<Value>, // String or number chosen by the client
+}
+
+impl RequestMeta {
+    /// Extracts `_meta` from raw request params, if present and well-formed.
+    pub fn from_params(params: Option<&Value>) -> Option<Self> {
+        params
+            .and_then(|p| p.get("_meta"))
+            .and_then(|meta| serde_json::from_value(meta.clone()).ok())
+    }
 }
 
 // --- Tool Call Specific Structs ---
@@ -138,27 +514,489 @@ pub struct ListPromptsResult {
 #[serde(rename_all = "camelCase")]
 pub struct CallToolRequestParams {
     pub name: String,      // Name of the tool being called
-    pub arguments: Value,  // Arguments for the tool (use Value for flexibility)
+    #[serde(default)]
+    pub arguments: Option<Value>, // Optional per the spec; checked against the tool's input_schema
+    // `_meta` is read from the raw params by RequestMeta::from_params, like for any request
 }
 
-#[derive(Serialize, Debug, Clone)]
+// --- Content Structs ---
+
+// Shared by tool results, prompt messages and sampling messages
+
+/// Hints on who content is meant for and how important it is.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
-pub struct ContentPart {
-    #[serde(rename = "type")] // Need to rename the field 'type'
-    pub type_: String, // e.g., "text", "image", etc.
+pub struct Annotations {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub text: Option<String>,
-    // ... other potential fields like uri, language, etc.
+    pub audience: Option<Vec<Role>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<f64>, // 0.0 (least important) to 1.0 (effectively required)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>, // ISO 8601 timestamp
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TextContent {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+/// Image or audio data, base64-encoded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaContent {
+    pub data: String,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+/// Resource contents (text or blob) inlined into a message.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddedResource {
+    pub resource: ResourceContents,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+/// A reference to a resource the client can read itself, without its contents.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLink {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text(TextContent),
+    Image(MediaContent),
+    Audio(MediaContent),
+    Resource(EmbeddedResource),
+    ResourceLink(ResourceLink), // Not allowed in sampling messages
+}
+
+impl ContentPart {
+    /// Replaces content a client speaking `version` can't parse with text describing it.
+    pub fn for_version(self, version: ProtocolVersion) -> Self {
+        match self {
+            Self::Audio(audio) if !version.supports_audio() => Self::Text(TextContent {
+                text: format!("[{} audio omitted]", audio.mime_type),
+                annotations: audio.annotations,
+            }),
+            Self::ResourceLink(link) if !version.supports_resource_links() => Self::Text(TextContent {
+                text: format!("[resource {}: {}]", link.name, link.uri),
+                annotations: link.annotations,
+            }),
+            part => part,
+        }
+    }
+
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(TextContent {
+            text: text.into(),
+            annotations: None,
+        })
+    }
+
+    /// An image, e.g. a screenshot; `data` is base64-encoded for the wire.
+    pub fn image(data: &[u8], mime_type: impl Into<String>) -> Self {
+        Self::Image(MediaContent::new(data, mime_type))
+    }
+
+    /// An audio clip; `data` is base64-encoded for the wire. Clients older than 2025-03-26
+    /// are sent a text placeholder instead.
+    pub fn audio(data: &[u8], mime_type: impl Into<String>) -> Self {
+        Self::Audio(MediaContent::new(data, mime_type))
+    }
+
+    pub fn resource(contents: ResourceContents) -> Self {
+        Self::Resource(EmbeddedResource {
+            resource: contents,
+            annotations: None,
+        })
+    }
+
+    /// A link to a resource, e.g. one of those listed by `resources/list`. Clients older
+    /// than 2025-06-18 are sent its name and URI as text instead.
+    pub fn resource_link(resource: &Resource) -> Self {
+        Self::ResourceLink(ResourceLink {
+            uri: resource.uri.clone(),
+            name: resource.name.clone(),
+            description: resource.description.clone(),
+            mime_type: resource.mime_type.clone(),
+            annotations: None,
+        })
+    }
+
+    /// Replaces the annotations on any kind of content.
+    pub fn with_annotations(mut self, annotations: Annotations) -> Self {
+        let slot = match &mut self {
+            Self::Text(c) => &mut c.annotations,
+            Self::Image(c) | Self::Audio(c) => &mut c.annotations,
+            Self::Resource(c) => &mut c.annotations,
+            Self::ResourceLink(c) => &mut c.annotations,
+        };
+        *slot = Some(annotations);
+        self
+    }
+
+    /// The text, if this is text content.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(c) => Some(&c.text),
+            _ => None,
+        }
+    }
+}
+
+impl MediaContent {
+    fn new(data: &[u8], mime_type: impl Into<String>) -> Self {
+        use base64::Engine;
+        Self {
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+            mime_type: mime_type.into(),
+            annotations: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CallToolResult {
     pub content: Vec<ContentPart>, // Result content
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>, // Must match the tool's output_schema, if it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub is_error: Option<bool>,    // Optional flag for tool errors
 }
 
+impl CallToolResult {
+    /// A result carrying `value` as structured content, plus the same JSON as text for
+    /// clients that predate structured content.
+    pub fn structured(value: Value) -> Self {
+        Self {
+            content: vec![ContentPart::text(value.to_string())],
+            structured_content: Some(value),
+            is_error: None,
+        }
+    }
+
+    /// Downgrades the result for a client speaking `version`.
+    ///
+    /// Structured content is dropped for clients that predate it, leaving its JSON as
+    /// text if the result has no other content.
+    pub fn for_version(self, version: ProtocolVersion) -> Self {
+        let mut content: Vec<_> = self.content.into_iter().map(|part| part.for_version(version)).collect();
+        let mut structured_content = self.structured_content;
+        if !version.supports_structured_content() {
+            if let Some(value) = structured_content.take() {
+                if content.is_empty() {
+                    content.push(ContentPart::text(value.to_string()));
+                }
+            }
+        }
+        Self {
+            content,
+            structured_content,
+            is_error: self.is_error,
+        }
+    }
+}
+
+// --- Prompt Get Specific Structs ---
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPromptRequestParams {
+    pub name: String,
+    pub arguments: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: ContentPart,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPromptResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub messages: Vec<PromptMessage>,
+}
+
+impl GetPromptResult {
+    /// Downgrades message content for a client speaking `version`; see [`ContentPart::for_version`].
+    pub fn for_version(self, version: ProtocolVersion) -> Self {
+        let messages = self
+            .messages
+            .into_iter()
+            .map(|message| PromptMessage {
+                content: message.content.for_version(version),
+                ..message
+            })
+            .collect();
+        Self { messages, ..self }
+    }
+}
+
+// --- Sampling Specific Structs ---
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: ContentPart, // Text, image or audio
+}
+
+impl SamplingMessage {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: ContentPart::text(text),
+        }
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: ContentPart::text(text),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelHint {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // Substring of a model name, e.g. "claude-3-5-sonnet"
+}
+
+// Priorities are 0.0 to 1.0; the client decides how to weigh them
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPreferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hints: Option<Vec<ModelHint>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intelligence_priority: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum IncludeContext {
+    None,
+    ThisServer,
+    AllServers,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageRequestParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<ModelPreferences>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<IncludeContext>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>, // Provider-specific, passed through by the client
+}
+
+impl CreateMessageRequestParams {
+    pub fn new(messages: Vec<SamplingMessage>, max_tokens: u32) -> Self {
+        Self {
+            messages,
+            max_tokens,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: ContentPart,
+    pub model: String, // The model the client actually used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>, // e.g. "endTurn", "stopSequence", "maxTokens"
+}
+
+// --- Roots Specific Structs ---
+
+/// A directory or file the client has opened and the server may operate on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Root {
+    pub uri: String, // Currently always a file:// URI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListRootsResult {
+    pub roots: Vec<Root>,
+}
+
+// --- Elicitation Specific Structs ---
+
+// Elicitation schemas are restricted to a flat object of primitive properties
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StringSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>, // "email", "uri", "date" or "date-time"
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<String>>, // Restricts the answer to these values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enum_names: Option<Vec<String>>, // Display names for enum_values
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NumberSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BooleanSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PrimitiveSchema {
+    String(StringSchema),
+    Number(NumberSchema),
+    Integer(NumberSchema),
+    Boolean(BooleanSchema),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitationSchema {
+    #[serde(rename = "type")]
+    pub type_: String, // Always "object"
+    pub properties: BTreeMap<String, PrimitiveSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub required: Vec<String>,
+}
+
+impl Default for ElicitationSchema {
+    fn default() -> Self {
+        Self {
+            type_: "object".to_string(),
+            properties: BTreeMap::new(),
+            required: Vec::new(),
+        }
+    }
+}
+
+impl ElicitationSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a property, marking it required if asked to.
+    pub fn property(mut self, name: impl Into<String>, schema: PrimitiveSchema, required: bool) -> Self {
+        let name = name.into();
+        if required {
+            self.required.push(name.clone());
+        }
+        self.properties.insert(name, schema);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitRequestParams {
+    pub message: String, // Shown to the user
+    pub requested_schema: ElicitationSchema,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitAction {
+    Accept,  // User submitted the form
+    Decline, // User explicitly said no
+    Cancel,  // User dismissed the dialog without choosing
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ElicitResult {
+    pub action: ElicitAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<serde_json::Map<String, Value>>, // Only present on accept
+}
+
+impl ElicitResult {
+    /// Deserializes the submitted form into `T`, or `None` if the user didn't accept.
+    pub fn content_as<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<Option<T>> {
+        match (&self.action, &self.content) {
+            (ElicitAction::Accept, Some(content)) => {
+                serde_json::from_value(Value::Object(content.clone())).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
 // --- Notification Structs (Example: Initialized) ---
 // While "initialized" doesn't have specific params in the current spec,
 // defining a struct can be useful for consistency if params are added later.
@@ -168,10 +1006,98 @@ pub struct InitializedNotificationParams {
     // Currently empty, but could hold info in future protocol versions
 }
 
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressNotificationParams {
+    pub progress_token: Value,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelledNotificationParams {
+    pub request_id: Value, // Id of the request to cancel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
 // Generic Notification struct (similar to GenericRequest but no ID expected in response)
-#[derive(Deserialize, Debug)]
+// Also used for notifications the server sends to the client.
+#[derive(Serialize, Deserialize, Debug)]
 pub struct GenericNotification {
     pub jsonrpc: String,
     pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn unsupported_content_becomes_text() {
+        let audio = ContentPart::audio(b"RIFF", "audio/wav");
+        assert!(matches!(audio.clone().for_version(ProtocolVersion::V2024_11_05), ContentPart::Text(_)));
+        assert!(matches!(audio.for_version(ProtocolVersion::V2025_03_26), ContentPart::Audio(_)));
+
+        let link = ContentPart::resource_link(&Resource {
+            uri: "file:///a.txt".to_string(),
+            name: "a.txt".to_string(),
+            description: None,
+            mime_type: None,
+        });
+        let text = link.clone().for_version(ProtocolVersion::V2025_03_26);
+        assert_eq!(text.as_text(), Some("[resource a.txt: file:///a.txt]"));
+        assert!(matches!(link.for_version(ProtocolVersion::V2025_06_18), ContentPart::ResourceLink(_)));
+    }
+
+    #[test]
+    fn tools_lose_output_schema_for_older_clients() {
+        let tool = Tool {
+            output_schema: Some(json!({ "type": "object" })),
+            ..Default::default()
+        };
+        assert!(tool.clone().for_version(ProtocolVersion::V2025_03_26).output_schema.is_none());
+        assert!(tool.for_version(ProtocolVersion::V2025_06_18).output_schema.is_some());
+    }
+
+    #[test]
+    fn tools_lose_title_and_annotations_for_older_clients() {
+        let tool = Tool {
+            title: Some("T".to_string()),
+            annotations: Some(ToolAnnotations::default()),
+            ..Default::default()
+        };
+
+        let downgraded = tool.clone().for_version(ProtocolVersion::V2024_11_05);
+        assert!(downgraded.title.is_none() && downgraded.annotations.is_none());
+        let downgraded = tool.clone().for_version(ProtocolVersion::V2025_03_26);
+        assert!(downgraded.title.is_none() && downgraded.annotations.is_some());
+        let downgraded = tool.for_version(ProtocolVersion::V2025_06_18);
+        assert!(downgraded.title.is_some() && downgraded.annotations.is_some());
+    }
+
+    #[test]
+    fn structured_content_falls_back_to_text() {
+        let result = CallToolResult::structured(json!({ "n": 1 })).for_version(ProtocolVersion::V2025_03_26);
+        assert!(result.structured_content.is_none());
+        assert_eq!(result.content.len(), 1);
+
+        let result = CallToolResult {
+            structured_content: Some(json!({ "n": 1 })),
+            ..Default::default()
+        }
+        .for_version(ProtocolVersion::V2025_03_26);
+        assert!(result.structured_content.is_none());
+        assert_eq!(result.content[0].as_text(), Some(r#"{"n":1}"#));
+
+        let result = CallToolResult::structured(json!({ "n": 1 })).for_version(ProtocolVersion::V2025_06_18);
+        assert!(result.structured_content.is_some());
+    }
+}
@@ -0,0 +1,437 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+// --- RFC 6570 URI Templates (matching only) ---
+//
+// Templates are only ever used here to recognise incoming URIs, never to expand
+// them, so this implements the reverse of RFC 6570 expansion: given a URI, find
+// values for the template variables that would have produced it. All operators
+// (`+ # . / ; ? &`) are understood. Explode (`*`) and prefix (`:n`) modifiers are
+// accepted, but an exploded variable is bound to the joined string of all the
+// items it absorbed (e.g. `a/b` for `{/path*}`) rather than to a list, and
+// prefixes are not checked.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Simple,    // {var}
+    Reserved,  // {+var}
+    Fragment,  // {#var}
+    Label,     // {.var}
+    Path,      // {/var}
+    PathParam, // {;var}
+    Query,     // {?var}
+    QueryCont, // {&var}
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '+' => Some(Self::Reserved),
+            '#' => Some(Self::Fragment),
+            '.' => Some(Self::Label),
+            '/' => Some(Self::Path),
+            ';' => Some(Self::PathParam),
+            '?' => Some(Self::Query),
+            '&' => Some(Self::QueryCont),
+            _ => None,
+        }
+    }
+
+    fn prefix(self) -> Option<char> {
+        match self {
+            Self::Simple | Self::Reserved => None,
+            Self::Fragment => Some('#'),
+            Self::Label => Some('.'),
+            Self::Path => Some('/'),
+            Self::PathParam => Some(';'),
+            Self::Query => Some('?'),
+            Self::QueryCont => Some('&'),
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Self::Simple | Self::Reserved | Self::Fragment => ',',
+            Self::Label => '.',
+            Self::Path => '/',
+            Self::PathParam => ';',
+            Self::Query | Self::QueryCont => '&',
+        }
+    }
+
+    fn is_named(self) -> bool {
+        matches!(self, Self::PathParam | Self::Query | Self::QueryCont)
+    }
+
+    /// Whether an expanded value may contain reserved characters as-is.
+    fn allows_reserved(self) -> bool {
+        matches!(self, Self::Reserved | Self::Fragment)
+    }
+
+    /// Whether `c` can appear anywhere in this operator's expansion.
+    fn allows_char(self, c: char) -> bool {
+        if self.allows_reserved() {
+            return !c.is_whitespace();
+        }
+        c.is_ascii_alphanumeric()
+            || matches!(c, '-' | '.' | '_' | '~' | '%')
+            || c == self.separator()
+            || (self.is_named() && c == '=')
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    name: String,
+    explode: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Expression {
+    operator: Operator,
+    variables: Vec<Variable>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Expression(Expression),
+}
+
+/// A parsed URI template such as `db://tables/{table}/rows/{id}`.
+#[derive(Debug, Clone)]
+pub struct UriTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+impl UriTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .map(|i| open + i)
+                .ok_or_else(|| anyhow!("unclosed expression in uri template: {}", template))?;
+            parts.push(Part::Expression(parse_expression(&rest[open + 1..close], template)?));
+            rest = &rest[close + 1..];
+        }
+        if rest.contains('}') {
+            bail!("unmatched '}}' in uri template: {}", template);
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Names of all variables, in template order.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().flat_map(|part| match part {
+            Part::Expression(expr) => expr.variables.iter().map(|v| v.name.as_str()).collect(),
+            Part::Literal(_) => Vec::new(),
+        })
+    }
+
+    /// Matches `uri` against the template, returning the (percent-decoded) variable values.
+    ///
+    /// Variables that were not present in the URI (e.g. an omitted query parameter) are
+    /// absent from the map. A variable standing alone in a `{var}` or `{/var}` expression
+    /// never matches an empty string, so it is always present when the URI matches.
+    pub fn match_uri(&self, uri: &str) -> Option<HashMap<String, String>> {
+        let mut variables = HashMap::new();
+        if match_parts(&self.parts, uri, &mut variables) {
+            Some(variables)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for UriTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+fn parse_expression(body: &str, template: &str) -> Result<Expression> {
+    let mut chars = body.chars();
+    let (operator, list) = match chars.next().and_then(Operator::from_char) {
+        Some(op) => (op, chars.as_str()),
+        None => (Operator::Simple, body),
+    };
+
+    let mut variables = Vec::new();
+    for spec in list.split(',') {
+        // Prefix modifiers are dropped; see the module comment
+        let explode = spec.ends_with('*');
+        let name = spec.trim_end_matches('*');
+        let name = name.split(':').next().unwrap_or(name);
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '%');
+        if !valid {
+            bail!("invalid variable '{}' in uri template: {}", spec, template);
+        }
+        variables.push(Variable {
+            name: name.to_string(),
+            explode,
+        });
+    }
+
+    Ok(Expression { operator, variables })
+}
+
+fn match_parts(parts: &[Part], input: &str, variables: &mut HashMap<String, String>) -> bool {
+    let Some((part, remaining_parts)) = parts.split_first() else {
+        return input.is_empty();
+    };
+
+    match part {
+        Part::Literal(literal) => match input.strip_prefix(literal.as_str()) {
+            Some(rest) => match_parts(remaining_parts, rest, variables),
+            None => false,
+        },
+        Part::Expression(expr) => {
+            // Longest run of characters this expression could have produced
+            let prefix = expr.operator.prefix();
+            let max_len = input
+                .char_indices()
+                .find(|&(i, c)| !(expr.operator.allows_char(c) || (i == 0 && Some(c) == prefix)))
+                .map(|(i, _)| i)
+                .unwrap_or(input.len());
+
+            // Greedy first, backing off until the rest of the template matches
+            let candidates = (0..=max_len).rev().filter(|&len| input.is_char_boundary(len));
+            for len in candidates {
+                let Some(bound) = bind_expression(expr, &input[..len]) else {
+                    continue;
+                };
+                let mut attempt = variables.clone();
+                attempt.extend(bound);
+                if match_parts(remaining_parts, &input[len..], &mut attempt) {
+                    *variables = attempt;
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Works out the variable values that expand to exactly `span`, if any.
+fn bind_expression(expr: &Expression, span: &str) -> Option<Vec<(String, String)>> {
+    // A lone `{var}` or `{/var}` stands for a whole path segment, which can't be empty
+    let single_segment = matches!(expr.operator, Operator::Simple | Operator::Path) && expr.variables.len() == 1;
+    if span.is_empty() {
+        return (!single_segment).then(Vec::new); // All variables undefined
+    }
+
+    let body = match expr.operator.prefix() {
+        Some(prefix) => span.strip_prefix(prefix)?,
+        None => span,
+    };
+
+    // Reserved expansions of a single variable may legitimately contain the separator
+    let values: Vec<&str> = if expr.operator.allows_reserved() && expr.variables.len() == 1 {
+        vec![body]
+    } else {
+        body.split(expr.operator.separator()).collect()
+    };
+
+    let separator = expr.operator.separator().to_string();
+    let exploded = expr.variables.iter().find(|v| v.explode);
+    let mut bound: Vec<(String, String)> = Vec::new();
+
+    if expr.operator.is_named() {
+        let mut extra = Vec::new(); // Pairs for an exploded variable, kept raw
+        for pair in values {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match expr.variables.iter().find(|v| v.name == name && !v.explode) {
+                Some(var) => bound.push((var.name.clone(), percent_decode(value)?)),
+                None if exploded.is_some() => extra.push(pair),
+                None => return None,
+            }
+        }
+        if let (Some(var), false) = (exploded, extra.is_empty()) {
+            bound.push((var.name.clone(), percent_decode(&extra.join(&separator))?));
+        }
+    } else {
+        let mut values = values.into_iter();
+        for (index, var) in expr.variables.iter().enumerate() {
+            // An exploded variable swallows whatever the variables after it don't need
+            let remaining_vars = expr.variables.len() - index - 1;
+            let value = if var.explode {
+                let take = values.len().saturating_sub(remaining_vars).max(1);
+                let items: Vec<&str> = values.by_ref().take(take).collect();
+                if items.is_empty() {
+                    break;
+                }
+                items.join(&separator)
+            } else {
+                match values.next() {
+                    Some(value) => value.to_string(),
+                    None => break,
+                }
+            };
+            bound.push((var.name.clone(), percent_decode(&value)?));
+        }
+        if values.next().is_some() {
+            return None;
+        }
+        if single_segment && bound.iter().any(|(_, value)| value.is_empty()) {
+            return None;
+        }
+    }
+    Some(bound)
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            // from_str_radix alone would also take a sign, e.g. "%+f"
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(template: &str, uri: &str) -> Option<HashMap<String, String>> {
+        UriTemplate::parse(template).unwrap().match_uri(uri)
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn simple() {
+        assert_eq!(
+            matches("db://tables/{table}/rows/{id}", "db://tables/users/rows/42"),
+            Some(vars(&[("table", "users"), ("id", "42")]))
+        );
+        assert_eq!(matches("db://tables/{table}", "db://other/users"), None);
+        assert_eq!(matches("db://tables/{table}", "db://tables/a/b"), None);
+    }
+
+    #[test]
+    fn empty_segments_do_not_match() {
+        assert_eq!(matches("db://tables/{table}/rows/{id}", "db://tables//rows/42"), None);
+        assert_eq!(matches("db://tables/{table}", "db://tables/"), None);
+        assert_eq!(matches("repo{/owner}", "repo/"), None);
+    }
+
+    #[test]
+    fn reserved() {
+        assert_eq!(matches("file://{+path}", "file:///a/b/c"), Some(vars(&[("path", "/a/b/c")])));
+    }
+
+    #[test]
+    fn fragment() {
+        assert_eq!(matches("doc{#section}", "doc#intro"), Some(vars(&[("section", "intro")])));
+    }
+
+    #[test]
+    fn label() {
+        assert_eq!(matches("file{.ext}", "file.json"), Some(vars(&[("ext", "json")])));
+    }
+
+    #[test]
+    fn path() {
+        assert_eq!(matches("repo{/owner,name}", "repo/a/b"), Some(vars(&[("owner", "a"), ("name", "b")])));
+        assert_eq!(matches("files{/path*}", "files/a/b/c"), Some(vars(&[("path", "a/b/c")])));
+    }
+
+    #[test]
+    fn path_param() {
+        assert_eq!(matches("map{;x,y}", "map;x=1;y=2"), Some(vars(&[("x", "1"), ("y", "2")])));
+    }
+
+    #[test]
+    fn query() {
+        assert_eq!(
+            matches("search{?q,limit}", "search?q=rust&limit=10"),
+            Some(vars(&[("q", "rust"), ("limit", "10")]))
+        );
+        assert_eq!(matches("search{?q,limit}", "search?q=rust"), Some(vars(&[("q", "rust")])));
+        assert_eq!(matches("search{?q,limit}", "search"), Some(vars(&[])));
+        assert_eq!(matches("search{?q}", "search?other=1"), None);
+    }
+
+    #[test]
+    fn query_continuation() {
+        assert_eq!(matches("search?q=a{&page}", "search?q=a&page=2"), Some(vars(&[("page", "2")])));
+    }
+
+    #[test]
+    fn backtracks_between_adjacent_expressions() {
+        assert_eq!(matches("{a}{b}", "xy"), Some(vars(&[("a", "x"), ("b", "y")])));
+        assert_eq!(
+            matches("{name}.{ext}", "archive.tar.gz"),
+            Some(vars(&[("name", "archive.tar"), ("ext", "gz")]))
+        );
+        assert_eq!(
+            matches("files{/dir*}{/file}", "files/a/b/c.txt"),
+            Some(vars(&[("dir", "a/b"), ("file", "c.txt")]))
+        );
+    }
+
+    #[test]
+    fn percent_decodes_values() {
+        assert_eq!(matches("notes/{title}", "notes/hello%20world"), Some(vars(&[("title", "hello world")])));
+        assert_eq!(matches("notes/{title}", "notes/caf%C3%A9"), Some(vars(&[("title", "café")])));
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        assert_eq!(matches("notes/{title}", "notes/100%"), None);
+        assert_eq!(matches("notes/{title}", "notes/a%2"), None);
+        assert_eq!(matches("notes/{title}", "notes/%zz"), None);
+        assert_eq!(matches("notes/{title}", "notes/%FF"), None); // Not UTF-8
+        assert_eq!(matches("files/{+path}", "files/a%+f"), None);
+        assert_eq!(matches("files/{+path}", "files/a%-1"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(UriTemplate::parse("db://{table").is_err());
+        assert!(UriTemplate::parse("db://table}").is_err());
+        assert!(UriTemplate::parse("db://{}").is_err());
+        assert!(UriTemplate::parse("db://{a b}").is_err());
+    }
+
+    #[test]
+    fn lists_variables() {
+        let template = UriTemplate::parse("db://{table}/rows{?limit,offset}").unwrap();
+        assert_eq!(template.variables().collect::<Vec<_>>(), ["table", "limit", "offset"]);
+    }
+}